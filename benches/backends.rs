@@ -0,0 +1,110 @@
+// Insert, drop-removal and iteration across the storage backends, against a
+// map of `Weak`s keyed by address as the usual non-intrusive baseline.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use weakset::{DenseStorage, Entry, HashStorage, OpenStorage, Storage, WeakSet};
+
+const SIZES: [usize; 2] = [1_000, 50_000];
+
+fn entries(n: usize) -> Vec<Pin<Box<Entry<u64>>>> {
+    (0..n as u64).map(|i| Box::pin(Entry::new(i))).collect()
+}
+
+fn filled<S: Storage<Meta = ()> + Default>(entries: &[Pin<Box<Entry<u64>>>]) -> Pin<Box<WeakSet<u64, S>>> {
+    let set = Box::pin(WeakSet::default());
+    for entry in entries {
+        set.as_ref().insert(entry.as_ref());
+    }
+    set
+}
+
+fn rcs(n: usize) -> Vec<Rc<u64>> {
+    (0..n as u64).map(Rc::new).collect()
+}
+
+fn weak_map(rcs: &[Rc<u64>]) -> HashMap<*const u64, Weak<u64>> {
+    rcs.iter().map(|rc| (Rc::as_ptr(rc), Rc::downgrade(rc))).collect()
+}
+
+fn bench_backend<S: Storage<Meta = ()> + Default>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(name);
+    for &n in &SIZES {
+        group.bench_with_input(BenchmarkId::new("insert", n), &n, |b, &n| {
+            b.iter_batched(
+                || entries(n),
+                // the set goes first when the output is dropped, untimed
+                |entries| (filled::<S>(&entries), entries),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("drop", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    let entries = entries(n);
+                    (filled::<S>(&entries), entries)
+                },
+                |(set, entries)| {
+                    drop(entries);
+                    set
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        let members = entries(n);
+        let set = filled::<S>(&members);
+        group.bench_with_input(BenchmarkId::new("iter", n), &n, |b, _| {
+            b.iter(|| {
+                let mut sum = 0u64;
+                set.as_ref().for_each(|x| sum += x);
+                sum
+            })
+        });
+        drop(set);
+    }
+    group.finish();
+}
+
+fn bench_weak_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("weak-map");
+    for &n in &SIZES {
+        group.bench_with_input(BenchmarkId::new("insert", n), &n, |b, &n| {
+            b.iter_batched(|| rcs(n), |rcs| (weak_map(&rcs), rcs), BatchSize::LargeInput)
+        });
+        // A weak map doesn't notice drops, so removal has to be done by hand
+        group.bench_with_input(BenchmarkId::new("drop", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    let rcs = rcs(n);
+                    (weak_map(&rcs), rcs)
+                },
+                |(mut map, rcs)| {
+                    for rc in rcs {
+                        map.remove(&Rc::as_ptr(&rc));
+                    }
+                    map
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        let members = rcs(n);
+        let map = weak_map(&members);
+        group.bench_with_input(BenchmarkId::new("iter", n), &n, |b, _| {
+            b.iter(|| map.values().filter_map(Weak::upgrade).map(|rc| *rc).sum::<u64>())
+        });
+    }
+    group.finish();
+}
+
+fn backends(c: &mut Criterion) {
+    bench_backend::<DenseStorage>(c, "dense");
+    bench_backend::<HashStorage>(c, "hash");
+    bench_backend::<OpenStorage>(c, "open");
+    bench_weak_map(c);
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+// Widgets backed by DOM elements, kept in a registry that never owns them.
+// JS holds each widget through its wasm-bindgen handle, and calling `free()`
+// on that handle drops the widget's entry, which takes it out of the
+// registry; nothing has to unregister by hand.
+//
+//     cargo build --example dom --target wasm32-unknown-unknown
+//     wasm-bindgen --target web --out-dir pkg \
+//         target/wasm32-unknown-unknown/debug/examples/dom.wasm
+//
+// and then, from a page importing `pkg/dom.js`:
+//
+//     const a = new Widget("a"), b = new Widget("b");
+//     relabel_all("!");   // both elements now end in "!"
+//     a.free();           // `a` leaves the page and the registry
+//     widget_count();     // 1
+
+#[cfg(target_arch = "wasm32")]
+mod dom {
+    use std::pin::Pin;
+
+    use wasm_bindgen::prelude::*;
+    use weakset::{Entry, WeakSet};
+    use web_sys::Element;
+
+    thread_local! {
+        static WIDGETS: Pin<Box<WeakSet<Element>>> = Box::pin(WeakSet::new());
+    }
+
+    #[wasm_bindgen]
+    pub struct Widget {
+        entry: Pin<Box<Entry<Element>>>,
+    }
+
+    #[wasm_bindgen]
+    impl Widget {
+        #[wasm_bindgen(constructor)]
+        pub fn new(label: &str) -> Result<Widget, JsValue> {
+            let document = web_sys::window().and_then(|window| window.document()).ok_or("no document")?;
+            let element = document.create_element("div")?;
+            element.set_text_content(Some(label));
+            document.body().ok_or("no body")?.append_child(&element)?;
+            let entry = Box::pin(Entry::new(element));
+            WIDGETS.with(|widgets| widgets.as_ref().insert(entry.as_ref()));
+            Ok(Widget { entry })
+        }
+    }
+
+    impl Drop for Widget {
+        fn drop(&mut self) {
+            // the entry leaves the registry right after this
+            let element: &Element = &self.entry;
+            element.remove();
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn relabel_all(suffix: &str) {
+        WIDGETS.with(|widgets| {
+            widgets.as_ref().for_each(|element| {
+                let label = element.text_content().unwrap_or_default();
+                element.set_text_content(Some(&(label + suffix)));
+            })
+        });
+    }
+
+    #[wasm_bindgen]
+    pub fn widget_count() -> usize {
+        WIDGETS.with(|widgets| widgets.len())
+    }
+}
@@ -0,0 +1,55 @@
+/* C interface to the weakset crate, built with the `ffi` feature.
+ *
+ * A weakset_t holds weak references to entries. Each entry carries a
+ * pointer to the object it stands for; freeing the entry takes it out of
+ * its set, so embed one in each registered object and free it from the
+ * object's destructor.
+ *
+ * Every function but the two destructors (which accept NULL) needs valid
+ * pointers obtained from this interface. Nothing here is thread-safe: a set
+ * and its entries must stay on one thread. Entries may outlive their set
+ * and the other way round.
+ */
+
+#ifndef WEAKSET_H
+#define WEAKSET_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct weakset weakset_t;
+typedef struct weakset_entry weakset_entry_t;
+
+typedef void (*weakset_callback_t)(void *data, void *ctx);
+
+weakset_t *weakset_new(void);
+/* Entries still in the set are detached, not freed */
+void weakset_free(weakset_t *set);
+
+weakset_entry_t *weakset_entry_new(void *data);
+/* Takes the entry out of its set, if any */
+void weakset_entry_free(weakset_entry_t *entry);
+void *weakset_entry_data(const weakset_entry_t *entry);
+bool weakset_entry_is_registered(const weakset_entry_t *entry);
+
+/* Returns false, doing nothing, if the entry already is in a set */
+bool weakset_insert(const weakset_t *set, const weakset_entry_t *entry);
+/* Returns false if the entry wasn't in this set */
+bool weakset_remove(const weakset_t *set, const weakset_entry_t *entry);
+size_t weakset_len(const weakset_t *set);
+
+/* Calls `callback` with the data of every entry and `ctx`. The callback may
+ * insert, remove and free entries; ones added meanwhile are left for the next
+ * call, and ones freed before their turn are skipped. Freeing the entry the
+ * callback was called for aborts the process, removing it is fine. */
+void weakset_for_each(const weakset_t *set, weakset_callback_t callback, void *ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,241 @@
+// Comparing registries, with the combinators of `HashSet`. The plain ones go
+// by entry identity; since an entry belongs to at most one set they mostly
+// tell whether two handles name the same set. The `_by_value` ones compare
+// payloads, for registries that hold equal values in different entries.
+//
+// The combinators return the ids of the members, in the order of the set
+// they come from, and a set holding the same value twice lists both. They
+// are collected under the sets' borrows, so the results can't dangle and
+// the sets are free to change while they are looked at.
+//
+// `==` also compares payloads, counting duplicates, against another set or
+// a list of expected values.
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::pin::Pin;
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{Entry, EntryId, Iter, Storage, WeakSet};
+
+// The payloads of one set, for lookups. Keeps that set borrowed, so none of
+// the payloads can go away while they are looked at.
+struct Values<'a, T, S: Storage> {
+    values: HashSet<&'a T>,
+    _guard: Iter<'a, T, S>,
+}
+
+impl<'a, T: Eq + Hash, S: Storage> Values<'a, T, S> {
+    fn new(set: Pin<&'a WeakSet<T, S>>) -> Self {
+        Values { values: set.iter().map(|entry| &entry.x).collect(), _guard: set.iter() }
+    }
+
+    fn contains(&self, x: &T) -> bool {
+        self.values.contains(x)
+    }
+}
+
+// The members found in only one of two sets, from `diff`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetDiff {
+    pub only_in_self: Vec<EntryId>,
+    pub only_in_other: Vec<EntryId>,
+}
+
+impl SetDiff {
+    // Whether both sets have the same members
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty()
+    }
+}
+
+fn ids<'a, T: 'a>(entries: impl Iterator<Item = &'a Entry<T>>) -> Vec<EntryId> {
+    entries.map(|entry| entry.id()).collect()
+}
+
+impl<T, S: Storage> WeakSet<T, S> {
+    // The members of this set, then those of `other` that aren't in it
+    pub fn union<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId> {
+        ids(self.iter().chain(other.iter().filter(|entry| !self.contains(entry))))
+    }
+
+    pub fn intersection<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId> {
+        ids(self.common(other))
+    }
+
+    pub fn difference<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId> {
+        ids(self.missing_from(other))
+    }
+
+    // The borrowed members behind `intersection` and `difference`, only to
+    // be looked at before the borrow ends
+    fn common<'a, S2: Storage>(self: Pin<&'a Self>, other: Pin<&'a WeakSet<T, S2>>)
+        -> impl Iterator<Item = &'a Entry<T>> + 'a
+    {
+        self.iter().filter(move |entry| other.contains(entry))
+    }
+
+    fn missing_from<'a, S2: Storage>(self: Pin<&'a Self>, other: Pin<&'a WeakSet<T, S2>>)
+        -> impl Iterator<Item = &'a Entry<T>> + 'a
+    {
+        self.iter().filter(move |entry| !other.contains(entry))
+    }
+
+    // The members of either set that aren't in the other one
+    pub fn symmetric_difference<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId> {
+        ids(self.missing_from(other).chain(other.missing_from(self)))
+    }
+
+    pub fn diff<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> SetDiff {
+        SetDiff { only_in_self: self.difference(other), only_in_other: other.difference(self) }
+    }
+
+    // The members of this set, then those of `other` whose value isn't in it
+    pub fn union_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId>
+    where T: Eq + Hash
+    {
+        let mine = Values::new(self);
+        ids(self.iter().chain(other.iter().filter(|entry| !mine.contains(entry))))
+    }
+
+    pub fn intersection_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId>
+    where T: Eq + Hash
+    {
+        ids(self.common_by_value(other))
+    }
+
+    pub fn difference_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId>
+    where T: Eq + Hash
+    {
+        ids(self.missing_from_by_value(other))
+    }
+
+    fn common_by_value<'a, S2: Storage>(self: Pin<&'a Self>, other: Pin<&'a WeakSet<T, S2>>)
+        -> impl Iterator<Item = &'a Entry<T>> + 'a
+    where T: Eq + Hash
+    {
+        let theirs = Values::new(other);
+        self.iter().filter(move |entry| theirs.contains(entry))
+    }
+
+    fn missing_from_by_value<'a, S2: Storage>(self: Pin<&'a Self>, other: Pin<&'a WeakSet<T, S2>>)
+        -> impl Iterator<Item = &'a Entry<T>> + 'a
+    where T: Eq + Hash
+    {
+        let theirs = Values::new(other);
+        self.iter().filter(move |entry| !theirs.contains(entry))
+    }
+
+    pub fn symmetric_difference_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> Vec<EntryId>
+    where T: Eq + Hash
+    {
+        ids(self.missing_from_by_value(other).chain(other.missing_from_by_value(self)))
+    }
+
+    // Which members have a value the other set lacks, e.g. to reconcile a
+    // registry with a list of what should be in it
+    pub fn diff_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> SetDiff
+    where T: Eq + Hash
+    {
+        SetDiff { only_in_self: self.difference_by_value(other), only_in_other: other.difference_by_value(self) }
+    }
+
+    // Take every member that is also in `other` out of this set, returning
+    // how many were removed
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn difference_update<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> usize {
+        let members = self.common(other).map(|entry| entry as *const Entry<T> as *const ()).collect();
+        self.remove_batch(members)
+    }
+
+    // Take out every member whose value occurs in `other`, e.g. a list of
+    // banned names
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn difference_update_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> usize
+    where T: Eq + Hash
+    {
+        let members = self.common_by_value(other).map(|entry| entry as *const Entry<T> as *const ()).collect();
+        self.remove_batch(members)
+    }
+
+    pub fn is_subset<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool {
+        self.iter().all(|entry| other.contains(entry))
+    }
+
+    pub fn is_superset<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool {
+        self.common(other).next().is_none()
+    }
+
+    // Whether every value in this set also occurs in `other`
+    pub fn is_subset_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool
+    where T: Eq + Hash
+    {
+        self.missing_from_by_value(other).next().is_none()
+    }
+
+    pub fn is_superset_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool
+    where T: Eq + Hash
+    {
+        other.is_subset_by_value(self)
+    }
+
+    pub fn is_disjoint_by_value<S2: Storage>(self: Pin<&Self>, other: Pin<&WeakSet<T, S2>>) -> bool
+    where T: Eq + Hash
+    {
+        self.common_by_value(other).next().is_none()
+    }
+}
+
+// The payloads of the storage of a set that is known to be borrowed
+fn values<'a, T: 'a, S: Storage>(objects: &'a S) -> impl Iterator<Item = &'a T> + 'a {
+    objects.iter().map(|(x, _)| unsafe { &(*(x as *const Entry<T>)).x })
+}
+
+// Whether both sides hold the same values, each the same number of times
+fn same_values<'a, T: Eq + Hash + 'a>(a: impl Iterator<Item = &'a T>, b: impl Iterator<Item = &'a T>) -> bool {
+    let mut counts: HashMap<&T, isize> = HashMap::new();
+    for x in a {
+        *counts.entry(x).or_insert(0) += 1;
+    }
+    for x in b {
+        match counts.get_mut(x) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => return false,
+        }
+    }
+    counts.values().all(|&n| n == 0)
+}
+
+// Only needs `&self`, like `Debug`: a set that was never pinned is empty
+impl<T: Eq + Hash, S: Storage, S2: Storage> PartialEq<WeakSet<T, S2>> for WeakSet<T, S> {
+    fn eq(&self, other: &WeakSet<T, S2>) -> bool {
+        let (mine, theirs) = (self.borrow_for_iter(), other.borrow_for_iter());
+        mine.len() == theirs.len() && same_values(values::<T, S>(&mine), values::<T, S2>(&theirs))
+    }
+}
+
+impl<T: Eq + Hash, S: Storage> Eq for WeakSet<T, S> {}
+
+impl<T: Eq + Hash, S: Storage> PartialEq<[T]> for WeakSet<T, S> {
+    fn eq(&self, other: &[T]) -> bool {
+        let mine = self.borrow_for_iter();
+        mine.len() == other.len() && same_values(values::<T, S>(&mine), other.iter())
+    }
+}
+
+impl<T: Eq + Hash, S: Storage, const N: usize> PartialEq<[T; N]> for WeakSet<T, S> {
+    fn eq(&self, other: &[T; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<T: Eq + Hash, S: Storage> PartialEq<Vec<T>> for WeakSet<T, S> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == other[..]
+    }
+}
@@ -0,0 +1,102 @@
+// Entries and member tables from a caller-supplied allocator, through
+// `allocator-api2`: its `Allocator` trait works on stable, and with the
+// `nightly` feature it is the standard library's own, so e.g. per-subsystem
+// arenas implementing either can be used. Only the member table comes from
+// the allocator; broadcast snapshots and the set's other bookkeeping stay
+// on the global heap.
+
+use core::pin::Pin;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec;
+
+use crate::storage::{DenseIter, DenseMembers, Storage};
+use crate::{Entry, WeakSet};
+
+// Allocate a pinned entry with `alloc`
+pub fn pin_entry<T, A: Allocator + 'static>(alloc: A, x: T) -> Pin<Box<Entry<T>, A>> {
+    Box::pin_in(Entry::new(x), alloc)
+}
+
+// `DenseStorage` with its array in `A`
+pub struct AllocStorage<A: Allocator, M = ()> {
+    members: Vec<(*const (), M), A>,
+}
+
+impl<A: Allocator, M> AllocStorage<A, M> {
+    pub fn new_in(alloc: A) -> Self {
+        AllocStorage { members: Vec::new_in(alloc) }
+    }
+}
+
+impl<A: Allocator, M> DenseMembers<M> for AllocStorage<A, M> {
+    fn members(&self) -> &[(*const (), M)] {
+        &self.members
+    }
+
+    fn members_mut(&mut self) -> &mut [(*const (), M)] {
+        &mut self.members
+    }
+
+    fn push(&mut self, member: (*const (), M)) {
+        self.members.push(member);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> (*const (), M) {
+        self.members.swap_remove(i)
+    }
+}
+
+unsafe impl<A: Allocator, M> Storage for AllocStorage<A, M> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where Self: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.dense_insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.dense_remove(entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense_get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense_get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        DenseIter::new(&self.members)
+    }
+
+    fn capacity(&self) -> usize {
+        self.members.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.members.reserve(additional);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.members.shrink_to(min);
+    }
+
+    // Memory from `A` rather than the heap, but still held on to by the set
+    fn heap_size(&self) -> usize {
+        self.members.capacity() * core::mem::size_of::<(*const (), M)>()
+    }
+}
+
+impl<T, A: Allocator, M> WeakSet<T, AllocStorage<A, M>> {
+    pub fn new_in(alloc: A) -> Self {
+        WeakSet::with_storage(AllocStorage::new_in(alloc))
+    }
+}
@@ -0,0 +1,37 @@
+// A registry of heterogeneous members. Payloads are boxed behind `dyn Any`
+// and retrieved by concrete type during iteration, so objects of unrelated
+// types can share one lifecycle registry.
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::pin::Pin;
+
+use crate::{DenseStorage, Entry, Storage, WeakSet};
+
+pub type AnyWeakSet<S = DenseStorage> = WeakSet<Box<dyn Any>, S>;
+
+impl Entry<Box<dyn Any>> {
+    pub fn new_any<U: Any>(x: U) -> Self {
+        Entry::new(Box::new(x))
+    }
+
+    pub fn downcast_ref<U: Any>(&self) -> Option<&U> {
+        self.x.downcast_ref()
+    }
+}
+
+impl<S: Storage> WeakSet<Box<dyn Any>, S> {
+    // Members whose payload is a `U`. Private like `iter`, since the items
+    // would outlive the borrow.
+    fn iter_of<U: Any>(self: Pin<&Self>) -> impl Iterator<Item = &U> {
+        self.iter().filter_map(|entry| entry.downcast_ref())
+    }
+
+    pub fn for_each_of<U: Any, F: FnMut(&U)>(self: Pin<&Self>, f: F) {
+        self.iter_of().for_each(f)
+    }
+
+    pub fn count_of<U: Any>(self: Pin<&Self>) -> usize {
+        self.iter_of::<U>().count()
+    }
+}
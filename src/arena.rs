@@ -0,0 +1,90 @@
+// Entries allocated from a typed arena owned by the caller. Arena memory never
+// moves and is only released when the arena itself is dropped (which drops
+// every entry in place), so entries are pinned for free and freed in bulk at
+// the end of the arena's scope.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::pin::Pin;
+
+use crate::{Entry, WeakSet};
+
+pub struct Arena<T> {
+    // Each chunk is allocated with a fixed capacity and never grows past it,
+    // so pushing into it doesn't move the entries already there.
+    chunks: RefCell<Vec<Vec<Entry<T>>>>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Arena::with_capacity(8)
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        Arena { chunks: RefCell::new(vec![Vec::with_capacity(n.max(1))]) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn alloc(&self, x: T) -> Pin<&Entry<T>> {
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks.last().unwrap();
+        if last.len() == last.capacity() {
+            let cap = last.capacity() * 2;
+            chunks.push(Vec::with_capacity(cap));
+        }
+        let chunk = chunks.last_mut().unwrap();
+        chunk.push(Entry::new(x));
+        let entry: *const Entry<T> = chunk.last().unwrap();
+        // The entry stays at this address until the arena drops it
+        unsafe { Pin::new_unchecked(&*entry) }
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+// A `WeakSet` whose members come from one arena. The set borrows the arena,
+// so it is always dropped first and none of its members can dangle.
+pub struct ArenaWeakSet<'a, T> {
+    arena: &'a Arena<T>,
+    set: Pin<Box<WeakSet<T>>>,
+}
+
+impl<'a, T> ArenaWeakSet<'a, T> {
+    pub fn new(arena: &'a Arena<T>) -> Self {
+        ArenaWeakSet { arena, set: Box::pin(WeakSet::new()) }
+    }
+
+    pub fn arena(&self) -> &'a Arena<T> {
+        self.arena
+    }
+
+    // Allocate a new entry from the arena and register it
+    pub fn insert(&self, x: T) -> Pin<&'a Entry<T>> {
+        let entry = self.arena.alloc(x);
+        self.set.as_ref().insert(entry);
+        entry
+    }
+
+    pub fn set(&self) -> Pin<&WeakSet<T>> {
+        self.set.as_ref()
+    }
+
+    // A closure rather than an iterator: `set` can also be given entries that
+    // don't come from the arena, which may drop at any time
+    pub fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.set.as_ref().for_each(f)
+    }
+}
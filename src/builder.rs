@@ -0,0 +1,91 @@
+// Configuring a set in one chain instead of constructing it and calling
+// setters afterwards:
+//
+//     let set = WeakSet::builder().capacity(1024).ordered().on_remove(f).build_pinned();
+//
+// Choosing a storage changes the builder's type, the other options can come
+// in any order.
+
+use alloc::boxed::Box;
+use core::pin::Pin;
+
+use crate::{DenseStorage, Hook, PriorityStorage, Storage, WeakSet};
+
+pub struct WeakSetBuilder<T, S: Storage = DenseStorage> {
+    storage: S,
+    capacity: usize,
+    auto_shrink: bool,
+    on_insert: Option<Hook<T>>,
+    on_remove: Option<Hook<T>>,
+}
+
+impl<T> WeakSet<T> {
+    pub fn builder() -> WeakSetBuilder<T> {
+        WeakSetBuilder::new(DenseStorage::new())
+    }
+}
+
+impl<T, S: Storage> WeakSetBuilder<T, S> {
+    pub fn new(storage: S) -> Self {
+        WeakSetBuilder { storage, capacity: 0, auto_shrink: true, on_insert: None, on_remove: None }
+    }
+
+    // Keep the members in another storage
+    pub fn storage<S2: Storage>(self, storage: S2) -> WeakSetBuilder<T, S2> {
+        WeakSetBuilder {
+            storage,
+            capacity: self.capacity,
+            auto_shrink: self.auto_shrink,
+            on_insert: self.on_insert,
+            on_remove: self.on_remove,
+        }
+    }
+
+    // Visit members in insertion order (`DenseStorage` reorders them on
+    // removal). Members can still be given priorities later.
+    pub fn ordered(self) -> WeakSetBuilder<T, PriorityStorage<S::Meta>> {
+        self.storage(PriorityStorage::default())
+    }
+
+    // Room for this many members up front, see `WeakSet::reserve`
+    pub fn capacity(mut self, n: usize) -> Self {
+        self.capacity = n;
+        self
+    }
+
+    // See `WeakSet::set_auto_shrink`
+    pub fn auto_shrink(mut self, on: bool) -> Self {
+        self.auto_shrink = on;
+        self
+    }
+
+    // See `WeakSet::on_insert`
+    pub fn on_insert<F>(mut self, f: F) -> Self
+    where F: FnMut(&T) + 'static
+    {
+        self.on_insert = Some(Box::new(f));
+        self
+    }
+
+    // See `WeakSet::on_remove`
+    pub fn on_remove<F>(mut self, f: F) -> Self
+    where F: FnMut(&T) + 'static
+    {
+        self.on_remove = Some(Box::new(f));
+        self
+    }
+
+    pub fn build(self) -> WeakSet<T, S> {
+        let WeakSetBuilder { mut storage, capacity, auto_shrink, on_insert, on_remove } = self;
+        storage.reserve(capacity);
+        let set = WeakSet::with_storage(storage);
+        set.auto_shrink.set(auto_shrink);
+        set.on_insert.set(on_insert);
+        set.on_remove.set(on_remove);
+        set
+    }
+
+    pub fn build_pinned(self) -> Pin<Box<WeakSet<T, S>>> {
+        Box::pin(self.build())
+    }
+}
@@ -0,0 +1,134 @@
+// `bumpalo` support for frame-based workloads: entries and the set's own
+// storage are carved out of a `Bump`, so a frame's registry costs no
+// individual heap allocations and is thrown away with the arena.
+//
+//     let frame = Bump::new();
+//     let set = Box::pin(WeakSet::<Node, BumpStorage>::new_in(&frame));
+//     let entry = bump::pin_entry(&frame, node);
+//     set.as_ref().insert_bump(&entry);
+//
+// Bump entries only join sets kept in a bump themselves. A forgotten entry is
+// never dropped, so it would stay registered after its bump frees it; a set
+// whose storage borrows a bump for as long as the entry's has to drop, and so
+// detach the entry, before that memory goes away.
+
+use core::ops::Deref;
+use core::pin::Pin;
+
+use bumpalo::boxed::Box;
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+
+use crate::storage::{DenseIter, DenseMembers, Storage};
+use crate::{Entry, EntryId, WeakSet};
+
+// A pinned entry allocated in a bump. Unlike plain bump allocations it is
+// still dropped (and so unlinked) when it goes away.
+pub struct BumpEntry<'b, T> {
+    entry: Pin<Box<'b, Entry<T>>>,
+}
+
+pub fn pin_entry<T>(bump: &Bump, x: T) -> BumpEntry<'_, T> {
+    BumpEntry { entry: Box::pin_in(Entry::new(x), bump) }
+}
+
+impl<'b, T> BumpEntry<'b, T> {
+    pub fn id(&self) -> EntryId {
+        self.entry.id()
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.entry.is_registered()
+    }
+}
+
+impl<'b, T> Deref for BumpEntry<'b, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.entry
+    }
+}
+
+// `DenseStorage` with its array in a bump
+pub struct BumpStorage<'b, M: 'b = ()> {
+    objects: Vec<'b, (*const (), M)>,
+}
+
+impl<'b, M> BumpStorage<'b, M> {
+    pub fn new_in(bump: &'b Bump) -> Self {
+        BumpStorage { objects: Vec::new_in(bump) }
+    }
+}
+
+impl<'b, M> DenseMembers<M> for BumpStorage<'b, M> {
+    fn members(&self) -> &[(*const (), M)] {
+        &self.objects
+    }
+
+    fn members_mut(&mut self) -> &mut [(*const (), M)] {
+        &mut self.objects
+    }
+
+    fn push(&mut self, member: (*const (), M)) {
+        self.objects.push(member);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> (*const (), M) {
+        self.objects.swap_remove(i)
+    }
+}
+
+unsafe impl<'b, M> Storage for BumpStorage<'b, M> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where Self: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.dense_insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.dense_remove(entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense_get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense_get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        DenseIter::new(&self.objects)
+    }
+
+    // Arena memory rather than heap, but still held on to by the set
+    fn heap_size(&self) -> usize {
+        self.objects.capacity() * core::mem::size_of::<(*const (), M)>()
+    }
+}
+
+impl<'b, T, M> WeakSet<T, BumpStorage<'b, M>> {
+    pub fn new_in(bump: &'b Bump) -> Self {
+        WeakSet::with_storage(BumpStorage::new_in(bump))
+    }
+
+    // The set is invariant in `'b`, so `entry`'s bump lives at least as long
+    // as the one the set is kept in
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert_bump(self: Pin<&Self>, entry: &BumpEntry<'b, T>) -> EntryId
+    where M: Default
+    {
+        self.insert(entry.entry.as_ref())
+    }
+
+    pub fn remove_bump(self: Pin<&Self>, entry: &BumpEntry<'b, T>) -> bool {
+        self.remove(entry.entry.as_ref())
+    }
+}
@@ -0,0 +1,56 @@
+// A topic-filtered event bus: one `Signal` per topic, created on first
+// subscription. Subscriptions are ordinary `Connection`s, so they end when
+// the subscriber drops them.
+
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::{Connection, Signal, Slot};
+
+pub struct EventBus<Topic, Msg: ?Sized> {
+    topics: RefCell<HashMap<Topic, Rc<Signal<Msg>>>>,
+}
+
+impl<Topic: Eq + Hash, Msg: ?Sized> EventBus<Topic, Msg> {
+    pub fn new() -> Self {
+        EventBus { topics: RefCell::new(HashMap::new()) }
+    }
+
+    pub fn subscribe<S: Slot<Msg> + 'static>(&self, topic: Topic, slot: S) -> Connection<Msg> {
+        let signal = self.topics.borrow_mut().entry(topic).or_default().clone();
+        signal.connect(slot)
+    }
+
+    // Deliver `msg` to the live subscribers of `topic`, returning false if
+    // there were none. Topics whose subscribers are all gone are forgotten.
+    pub fn publish<Q>(&self, topic: &Q, msg: &Msg) -> bool
+    where Topic: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        let signal = match self.topics.borrow().get(topic) {
+            Some(signal) => signal.clone(),
+            None => return false,
+        };
+        if signal.is_empty() {
+            self.topics.borrow_mut().remove(topic);
+            return false;
+        }
+        // the map isn't borrowed here, so subscribers may (un)subscribe
+        signal.emit(msg);
+        true
+    }
+
+    pub fn subscriber_count<Q>(&self, topic: &Q) -> usize
+    where Topic: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        self.topics.borrow().get(topic).map_or(0, |signal| signal.len())
+    }
+}
+
+impl<Topic: Eq + Hash, Msg: ?Sized> Default for EventBus<Topic, Msg> {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
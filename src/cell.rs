@@ -0,0 +1,106 @@
+// The cell holding a set's storage. A `RefCell` would check a borrow flag on
+// every access; here the full checks only exist in debug builds. Release
+// builds keep just what soundness needs: exclusive borrows never run user
+// code, so the only way to get overlapping access is a callback mutating the
+// set while a shared borrow (an iteration) is alive, and that is still
+// caught, with the check on the mutating side rather than on iteration.
+
+use core::cell::{Cell, UnsafeCell};
+use core::ops::{Deref, DerefMut};
+
+pub(crate) struct StorageCell<T> {
+    value: UnsafeCell<T>,
+    readers: Cell<usize>,
+    #[cfg(debug_assertions)]
+    writing: Cell<bool>,
+}
+
+impl<T> StorageCell<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        StorageCell {
+            value: UnsafeCell::new(value),
+            readers: Cell::new(0),
+            #[cfg(debug_assertions)]
+            writing: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn borrow(&self) -> StorageRef<'_, T> {
+        #[cfg(debug_assertions)]
+        assert!(!self.writing.get(), "set storage already mutably borrowed");
+        self.readers.set(self.readers.get() + 1);
+        StorageRef { cell: self }
+    }
+
+    // The caller must not run user code while holding the result
+    pub(crate) fn borrow_mut(&self) -> StorageRefMut<'_, T> {
+        if self.readers.get() != 0 {
+            panic!("set modified while it is being iterated");
+        }
+        #[cfg(debug_assertions)]
+        {
+            assert!(!self.writing.get(), "set storage already mutably borrowed");
+            self.writing.set(true);
+        }
+        StorageRefMut { cell: self }
+    }
+
+    // Whether an iteration holds the storage, so that it can't be changed
+    pub(crate) fn is_borrowed(&self) -> bool {
+        self.readers.get() != 0
+    }
+
+    pub(crate) fn try_borrow_mut(&self) -> Option<StorageRefMut<'_, T>> {
+        if self.readers.get() != 0 {
+            return None;
+        }
+        Some(self.borrow_mut())
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+pub(crate) struct StorageRef<'a, T> {
+    cell: &'a StorageCell<T>,
+}
+
+impl<'a, T> Deref for StorageRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<'a, T> Drop for StorageRef<'a, T> {
+    fn drop(&mut self) {
+        self.cell.readers.set(self.cell.readers.get() - 1);
+    }
+}
+
+pub(crate) struct StorageRefMut<'a, T> {
+    cell: &'a StorageCell<T>,
+}
+
+impl<'a, T> Deref for StorageRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for StorageRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<'a, T> Drop for StorageRefMut<'a, T> {
+    fn drop(&mut self) {
+        self.cell.writing.set(false);
+    }
+}
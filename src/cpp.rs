@@ -0,0 +1,76 @@
+// C++ bindings through `cxx`. A C++ object joins a `Registry` by holding the
+// `rust::Box<weakset::Member>` that `join` returns, usually as a field: the
+// box's destructor runs with the object's and takes the member out of the
+// registry, just like dropping an `Entry` on the Rust side. Members are
+// tagged with a `size_t` of the caller's choosing, typically the object's
+// address.
+//
+// The C++ header and glue come from `cxxbridge src/cpp.rs --header` and
+// `cxxbridge src/cpp.rs`, or from `cxx_build::bridge("src/cpp.rs")` in the
+// build script of a crate linking both sides.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::pin::Pin;
+
+use crate::{Entry, WeakSet};
+
+#[cxx::bridge(namespace = "weakset")]
+mod ffi {
+    extern "Rust" {
+        type Registry;
+        type Member;
+
+        fn new_registry() -> Box<Registry>;
+        fn join(self: &Registry, tag: usize) -> Box<Member>;
+        fn len(self: &Registry) -> usize;
+        // The tags of the current members. cxx can't pass callbacks into
+        // Rust yet, so this is a snapshot rather than a `broadcast`.
+        fn tags(self: &Registry) -> Vec<usize>;
+
+        fn tag(self: &Member) -> usize;
+        fn is_registered(self: &Member) -> bool;
+    }
+}
+
+pub struct Registry {
+    set: Pin<Box<WeakSet<usize>>>,
+}
+
+pub struct Member {
+    entry: Pin<Box<Entry<usize>>>,
+}
+
+fn new_registry() -> Box<Registry> {
+    Box::new(Registry { set: Box::pin(WeakSet::new()) })
+}
+
+impl Registry {
+    pub fn join(&self, tag: usize) -> Box<Member> {
+        let entry = Box::pin(Entry::new(tag));
+        self.set.as_ref().insert(entry.as_ref());
+        Box::new(Member { entry })
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    pub fn tags(&self) -> Vec<usize> {
+        self.set.as_ref().iter().map(|entry| **entry).collect()
+    }
+}
+
+impl Member {
+    pub fn tag(&self) -> usize {
+        **self.entry
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.entry.is_registered()
+    }
+}
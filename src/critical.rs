@@ -0,0 +1,140 @@
+// A set that can be shared with interrupt handlers on bare-metal targets.
+// Every operation on it, including a member's drop checking whether it is
+// still linked, runs inside `critical_section::with`, so e.g. a registry of
+// active DMA transfers can be updated from both thread mode and the
+// transfer-complete interrupt without a mutex:
+//
+//     static TRANSFERS: CriticalWeakSet<Transfer> = CriticalWeakSet::new();
+//
+//     Pin::static_ref(&TRANSFERS).insert(transfer.entry());
+//
+// `for_each` and `broadcast` keep interrupts masked while their callbacks
+// run, so those should be short.
+//
+// Members may belong to other contexts, so `remove_by_id`, `clear` and
+// dropping the set leave the members' `on_detached` hooks alone rather than
+// run (or drop) them where they might not be `Send`: they stay with the
+// entry, to run the next time it leaves a set through `remove` or its own
+// drop, or to be dropped along with it. Since an entry can't tell what kind of set
+// it is in, enabling the feature makes every entry's drop take a critical
+// section, whichever set it belongs to.
+
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+
+use crate::{DenseStorage, Entry, EntryId, Header, Storage, WeakSet};
+
+pub struct CriticalWeakSet<T, S: Storage = DenseStorage> {
+    // dropped in a critical section too, it detaches the members
+    set: ManuallyDrop<WeakSet<T, S>>,
+}
+
+// Only touched from within a critical section. Storages aren't `Send`
+// themselves since they hold entry pointers, but those are entries of `T`.
+// Callbacks on any context get a `&T` to a member owned by another, so `T`
+// has to be `Sync` as well.
+unsafe impl<T: Send + Sync, S: Storage> Sync for CriticalWeakSet<T, S>
+where S::Meta: Send {}
+
+impl<T> CriticalWeakSet<T> {
+    pub const fn new() -> Self {
+        CriticalWeakSet::with_storage(DenseStorage::new())
+    }
+}
+
+impl<T, S: Storage> CriticalWeakSet<T, S> {
+    pub const fn with_storage(storage: S) -> Self {
+        let mut set = WeakSet::with_storage(storage);
+        set.header.unlink = Self::unlink;
+        CriticalWeakSet { set: ManuallyDrop::new(set) }
+    }
+
+    unsafe fn unlink(header: *const Header<T>, entry: *const Entry<T>) {
+        critical_section::with(|_| WeakSet::<T, S>::unlink(header, entry))
+    }
+
+    fn set(self: Pin<&Self>) -> Pin<&WeakSet<T, S>> {
+        unsafe { self.map_unchecked(|this| &*this.set) }
+    }
+
+    pub fn insert(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> EntryId
+    where S::Meta: Default
+    {
+        critical_section::with(|_| self.set().insert(entry))
+    }
+
+    pub fn remove(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> bool {
+        critical_section::with(|_| self.set().remove(entry))
+    }
+
+    pub fn remove_by_id(self: Pin<&Self>, id: EntryId) -> bool {
+        critical_section::with(|_| {
+            let ptr = id.0 as *const ();
+            if !self.set.objects.borrow().contains_addr(ptr) {
+                return false;
+            }
+            // Members are live entries
+            self.detach(unsafe { Pin::new_unchecked(&*(ptr as *const Entry<T>)) })
+        })
+    }
+
+    pub fn clear(self: Pin<&Self>) {
+        critical_section::with(|_| self.detach_all())
+    }
+
+    // Take out a member that may belong to another context, without its hooks
+    fn detach(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> bool {
+        let set = self.set();
+        match set.take(entry) {
+            Some(meta) => {
+                drop(meta);
+                set.removed(&entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn detach_all(self: Pin<&Self>) {
+        let set = self.set();
+        set.dispatch_over(set.snapshot(), |entry| {
+            self.detach(entry);
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        critical_section::with(|_| self.set.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn for_each<F>(self: Pin<&Self>, f: F)
+    where F: FnMut(&T)
+    {
+        critical_section::with(|_| self.set().for_each(f))
+    }
+
+    pub fn broadcast<F>(self: Pin<&Self>, f: F)
+    where F: FnMut(&T)
+    {
+        critical_section::with(|_| self.set().broadcast(f))
+    }
+}
+
+impl<T, S: Storage> Drop for CriticalWeakSet<T, S> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            // Pinned if it ever had members, and this is its last use
+            unsafe { Pin::new_unchecked(&*self) }.detach_all();
+            unsafe { ManuallyDrop::drop(&mut self.set) }
+        })
+    }
+}
+
+impl<T, S: Storage + Default> Default for CriticalWeakSet<T, S> {
+    fn default() -> Self {
+        CriticalWeakSet::with_storage(S::default())
+    }
+}
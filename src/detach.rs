@@ -0,0 +1,94 @@
+// Finding out when an entry leaves its collection, whether it is dropped,
+// taken out with `remove`/`clear`, or the collection itself goes away. Handy
+// for "wait until this object is deregistered" in async code.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::{Entry, OnDetached};
+
+impl<T> Entry<T> {
+    // Run `f` the next time this entry leaves a collection. Unlike
+    // `on_final_drop`, hooks accumulate: every registered one runs, oldest
+    // first.
+    pub fn on_detached<F>(&self, f: F)
+    where F: FnOnce() + 'static
+    {
+        self.set_hooks(|hooks| {
+            let f: OnDetached = match hooks.detached.take() {
+                Some(earlier) => Box::new(move || {
+                    earlier();
+                    f();
+                }),
+                None => Box::new(f),
+            };
+            hooks.detached = Some(f);
+        });
+    }
+
+    // A future that resolves once this entry has left its collection. It is
+    // ready right away if the entry isn't registered anywhere, and it also
+    // resolves if the entry is dropped without being registered again.
+    pub fn detached_future(&self) -> Detached {
+        let state = Rc::new(State { done: Cell::new(!self.is_registered()), waker: RefCell::new(None) });
+        if !state.done.get() {
+            let notify = Notify(state.clone());
+            self.on_detached(move || drop(notify));
+        }
+        Detached { state }
+    }
+
+    pub(crate) fn fire_detached(&self) {
+        if let Some(f) = self.take_hook(|hooks| hooks.detached.take()) {
+            f();
+        }
+    }
+}
+
+struct State {
+    done: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+// Completes the future when dropped, which is either when the detach hook
+// runs or when the hook is discarded along with its entry
+struct Notify(Rc<State>);
+
+impl Drop for Notify {
+    fn drop(&mut self) {
+        self.0.done.set(true);
+        if let Some(waker) = self.0.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Detached {
+    state: Rc<State>,
+}
+
+impl Detached {
+    pub fn is_detached(&self) -> bool {
+        self.state.done.get()
+    }
+}
+
+impl Future for Detached {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.state.done.get() {
+            return Poll::Ready(());
+        }
+        let mut waker = self.state.waker.borrow_mut();
+        match &mut *waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            slot => *slot = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
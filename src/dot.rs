@@ -0,0 +1,90 @@
+// Graphviz export for debugging lifecycle bugs. Each set becomes a box with
+// an edge to every member, and each member a node labelled with its payload
+// and a dashed edge back to the collection its back-pointer names. In a
+// healthy registry every dashed edge leads back to the box it came from; a
+// red one means a member points somewhere else.
+//
+//     let mut graph = DotGraph::new();
+//     graph.add_set("widgets", widgets.as_ref());
+//     graph.add_set("timers", timers.as_ref());
+//     std::fs::write("registry.dot", graph.finish())?;
+
+use alloc::string::String;
+use core::fmt::{Debug, Write};
+use core::pin::Pin;
+
+use hashbrown::HashSet;
+
+use crate::{Storage, WeakSet};
+
+pub struct DotGraph {
+    out: String,
+    // entries already written, in case several sets list the same one
+    entries: HashSet<usize>,
+}
+
+impl DotGraph {
+    pub fn new() -> Self {
+        DotGraph { out: String::from("digraph weakset {\n"), entries: HashSet::new() }
+    }
+
+    pub fn add_set<T: Debug, S: Storage>(&mut self, name: &str, set: Pin<&WeakSet<T, S>>) -> &mut Self {
+        let id = &*set as *const WeakSet<T, S> as usize;
+        let _ = writeln!(self.out, "  s{:x} [shape=box, label=\"{} ({})\"];", id, escape(name), set.len());
+        for entry in set.iter() {
+            let entry_id = entry as *const _ as usize;
+            if self.entries.insert(entry_id) {
+                let label = escape(&alloc::format!("{:?}", entry.x));
+                let _ = writeln!(self.out, "  e{:x} [label=\"{}\"];", entry_id, label);
+                // the header is the set's first field, so they share an address
+                match entry.collection.get() {
+                    Some(header) if header.as_ptr() as usize == id => {
+                        let _ = writeln!(self.out, "  e{:x} -> s{:x} [style=dashed];", entry_id, id);
+                    }
+                    Some(header) => {
+                        let other = header.as_ptr() as usize;
+                        let _ = writeln!(self.out, "  e{:x} -> s{:x} [style=dashed, color=red];", entry_id, other);
+                    }
+                    None => {}
+                }
+            }
+            let _ = writeln!(self.out, "  s{:x} -> e{:x};", id, entry_id);
+        }
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("}\n");
+        self.out
+    }
+}
+
+impl Default for DotGraph {
+    fn default() -> Self {
+        DotGraph::new()
+    }
+}
+
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<T: Debug, S: Storage> WeakSet<T, S> {
+    // The set alone as a DOT graph; see `DotGraph` for several at once
+    pub fn to_dot(self: Pin<&Self>) -> String {
+        let mut graph = DotGraph::new();
+        graph.add_set("set", self);
+        graph.finish()
+    }
+}
@@ -0,0 +1,104 @@
+// A C interface over opaque pointers, declared in `include/weakset.h`. Each
+// entry carries a `void *` for the C object it stands for, and freeing the
+// entry (typically from the object's own destructor) takes it out of its
+// set, so a C registry never holds on to freed objects.
+//
+// Build the crate as a static or dynamic library with the `ffi` feature,
+// e.g. `cargo rustc --lib --release --features ffi --crate-type staticlib`.
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::pin::Pin;
+
+use crate::{Entry, WeakSet};
+
+pub type CSet = WeakSet<*mut c_void>;
+pub type CEntry = Entry<*mut c_void>;
+pub type Callback = unsafe extern "C" fn(data: *mut c_void, ctx: *mut c_void);
+
+// Boxes are never moved out of again, so the pointers handed out are pinned
+unsafe fn set<'a>(set: *const CSet) -> Pin<&'a CSet> {
+    Pin::new_unchecked(&*set)
+}
+
+unsafe fn entry<'a>(entry: *const CEntry) -> Pin<&'a CEntry> {
+    Pin::new_unchecked(&*entry)
+}
+
+#[no_mangle]
+pub extern "C" fn weakset_new() -> *mut CSet {
+    Box::into_raw(Box::new(WeakSet::new()))
+}
+
+// Safety: `set` must be null or come from `weakset_new`, and not be used
+// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_free(set: *mut CSet) {
+    if !set.is_null() {
+        drop(Box::from_raw(set));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn weakset_entry_new(data: *mut c_void) -> *mut CEntry {
+    Box::into_raw(Box::new(Entry::new(data)))
+}
+
+// Safety: `entry` must be null or come from `weakset_entry_new`, and not be
+// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_entry_free(entry: *mut CEntry) {
+    if !entry.is_null() {
+        drop(Box::from_raw(entry));
+    }
+}
+
+// Safety: `entry` must be a live entry from `weakset_entry_new`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_entry_data(entry: *const CEntry) -> *mut c_void {
+    **self::entry(entry)
+}
+
+// Safety: `entry` must be a live entry from `weakset_entry_new`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_entry_is_registered(entry: *const CEntry) -> bool {
+    self::entry(entry).is_registered()
+}
+
+// Fails instead of panicking when the entry already is in a set, since a
+// panic can't unwind into C.
+//
+// Safety: `set` and `entry` must be live, from `weakset_new` and
+// `weakset_entry_new`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_insert(set: *const CSet, entry: *const CEntry) -> bool {
+    let entry = self::entry(entry);
+    if entry.is_registered() {
+        return false;
+    }
+    self::set(set).insert(entry);
+    true
+}
+
+// Safety: `set` and `entry` must be live, from `weakset_new` and
+// `weakset_entry_new`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_remove(set: *const CSet, entry: *const CEntry) -> bool {
+    self::set(set).remove(self::entry(entry))
+}
+
+// Safety: `set` must be a live set from `weakset_new`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_len(set: *const CSet) -> usize {
+    self::set(set).len()
+}
+
+// Goes through `broadcast`, so the callback may insert, remove and free
+// other entries.
+//
+// Safety: `set` must be a live set from `weakset_new`, and `callback` must
+// be safe to call with each member's data and `ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn weakset_for_each(set: *const CSet, callback: Callback, ctx: *mut c_void) {
+    self::set(set).broadcast(|&data| callback(data, ctx));
+}
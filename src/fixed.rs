@@ -0,0 +1,121 @@
+// A fixed-capacity storage on `heapless` for targets without a heap. The
+// members live in an array inside the set, laid out like `DenseStorage`, so
+// inserting, removing and iterating never allocate. (Broadcasts still take
+// their snapshot on the heap, reusing it from then on, so allocation-free
+// code should stick to `for_each` and `iter`.)
+//
+// A full set can't take more members: `try_insert` says so, while plain
+// `insert` panics.
+//
+// This storage doesn't make the crate usable without `alloc`: the set's
+// hooks, broadcast snapshots and other bookkeeping are still built on it, so
+// a global allocator has to exist even where nothing ends up calling it.
+
+use core::pin::Pin;
+
+use heapless::Vec;
+
+use crate::storage::{DenseIter, DenseMembers, Storage};
+use crate::{Entry, WeakSet};
+
+pub struct FixedStorage<M = (), const N: usize = 8> {
+    members: Vec<(*const (), M), N>,
+}
+
+impl<M, const N: usize> Default for FixedStorage<M, N> {
+    fn default() -> Self {
+        FixedStorage::new()
+    }
+}
+
+impl<M, const N: usize> FixedStorage<M, N> {
+    pub const fn new() -> Self {
+        FixedStorage { members: Vec::new() }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.members.is_full()
+    }
+}
+
+impl<M, const N: usize> DenseMembers<M> for FixedStorage<M, N> {
+    fn members(&self) -> &[(*const (), M)] {
+        &self.members
+    }
+
+    fn members_mut(&mut self) -> &mut [(*const (), M)] {
+        &mut self.members
+    }
+
+    fn push(&mut self, member: (*const (), M)) {
+        if self.members.push(member).is_err() {
+            panic!("FixedStorage is full");
+        }
+    }
+
+    fn swap_remove(&mut self, i: usize) -> (*const (), M) {
+        self.members.swap_remove(i)
+    }
+}
+
+unsafe impl<M, const N: usize> Storage for FixedStorage<M, N> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.dense_insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.dense_remove(entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense_get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense_get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        DenseIter::new(&self.members)
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+}
+
+// Returned by `try_insert` when the set has no room left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full;
+
+impl<T, M, const N: usize> WeakSet<T, FixedStorage<M, N>> {
+    pub const fn new_fixed() -> Self {
+        WeakSet::with_storage(FixedStorage::new())
+    }
+
+    pub fn try_insert(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> Result<(), Full>
+    where M: Default
+    {
+        self.try_insert_with_meta(entry, M::default())
+    }
+
+    pub fn try_insert_with_meta(self: Pin<&Self>, entry: Pin<&Entry<T>>, meta: M) -> Result<(), Full> {
+        if self.objects.borrow().is_full() {
+            return Err(Full);
+        }
+        self.insert_with_meta(entry, meta);
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.objects.borrow().is_full()
+    }
+}
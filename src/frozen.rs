@@ -0,0 +1,71 @@
+// An immutable snapshot of a set's members at one point in time. The values
+// are cloned out once (for a set of `Arc<_>` handles that means the snapshot
+// keeps the members alive), after which the snapshot never touches the live
+// set again, can be iterated any number of times, and is as cheap to clone
+// and as `Send` as an `Arc<[T]>`.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::slice;
+
+use crate::{Storage, WeakSet};
+
+pub struct FrozenWeakSet<T> {
+    values: Arc<[T]>,
+}
+
+impl<T> FrozenWeakSet<T> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // In the live set's iteration order at the time of the snapshot
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.values.iter()
+    }
+}
+
+impl<T> Clone for FrozenWeakSet<T> {
+    fn clone(&self) -> Self {
+        FrozenWeakSet { values: self.values.clone() }
+    }
+}
+
+impl<T> Deref for FrozenWeakSet<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.values
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FrozenWeakSet<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FrozenWeakSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S: Storage> WeakSet<T, S> {
+    pub fn freeze(self: Pin<&Self>) -> FrozenWeakSet<T>
+    where T: Clone
+    {
+        let values: Vec<T> = self.clone_values();
+        FrozenWeakSet { values: values.into() }
+    }
+}
@@ -0,0 +1,179 @@
+// A directed graph over pinned entries, e.g. invalidation dependencies
+// between cached computations. The nodes are the members of a `WeakSet`, and
+// edges only exist between members: when a node drops (or is removed), every
+// edge from or to it goes with it, so no edge ever dangles.
+//
+//     let deps = WeakGraph::new();
+//     deps.insert(layout.as_ref());
+//     deps.insert(paint.as_ref());
+//     deps.add_edge(&layout, &paint);
+//     deps.for_each_successor(&layout, |node| node.invalidate());
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::mem;
+use core::pin::Pin;
+
+use hashbrown::HashSet;
+
+use crate::storage::PtrMap;
+use crate::{Entry, PtrBuildHasher, WeakSet};
+
+type PtrSet = HashSet<*const (), PtrBuildHasher>;
+
+pub struct WeakGraph<T> {
+    nodes: Pin<Box<WeakSet<T>>>,
+    // Shared with the node set's `on_remove` hook, which takes out the
+    // edges of nodes that leave
+    edges: Rc<RefCell<Edges>>,
+}
+
+// Both directions, keyed by entry address
+#[derive(Default)]
+struct Edges {
+    out: PtrMap<PtrSet>,
+    into: PtrMap<PtrSet>,
+    len: usize,
+}
+
+impl Edges {
+    fn insert(&mut self, from: *const (), to: *const ()) -> bool {
+        if !self.out.entry(from).or_default().insert(to) {
+            return false;
+        }
+        self.into.entry(to).or_default().insert(from);
+        self.len += 1;
+        true
+    }
+
+    fn remove(&mut self, from: *const (), to: *const ()) -> bool {
+        let removed = self.out.get_mut(&from).is_some_and(|succs| succs.remove(&to));
+        if removed {
+            if let Some(preds) = self.into.get_mut(&to) {
+                preds.remove(&from);
+            }
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn contains(&self, from: *const (), to: *const ()) -> bool {
+        self.out.get(&from).is_some_and(|succs| succs.contains(&to))
+    }
+
+    fn remove_node(&mut self, node: *const ()) {
+        for succ in self.out.remove(&node).unwrap_or_default() {
+            if let Some(preds) = self.into.get_mut(&succ) {
+                preds.remove(&node);
+            }
+            self.len -= 1;
+        }
+        for pred in self.into.remove(&node).unwrap_or_default() {
+            if let Some(succs) = self.out.get_mut(&pred) {
+                succs.remove(&node);
+            }
+            self.len -= 1;
+        }
+    }
+}
+
+fn addr<T>(entry: &Entry<T>) -> *const () {
+    entry as *const Entry<T> as *const ()
+}
+
+impl<T: 'static> WeakGraph<T> {
+    pub fn new() -> Self {
+        let edges: Rc<RefCell<Edges>> = Rc::default();
+        let nodes = Box::pin(WeakSet::new());
+        let hook = edges.clone();
+        nodes.on_remove(move |x: &T| {
+            // The hook sees the payload; the entry starts a fixed distance
+            // before it
+            let entry = (x as *const T as *const u8).wrapping_sub(mem::offset_of!(Entry<T>, x));
+            hook.borrow_mut().remove_node(entry as *const ());
+        });
+        WeakGraph { nodes, edges }
+    }
+
+    pub fn insert(&self, node: Pin<&Entry<T>>) {
+        self.nodes.as_ref().insert(node);
+    }
+
+    // Take `node` out along with its edges
+    pub fn remove(&self, node: Pin<&Entry<T>>) -> bool {
+        self.nodes.as_ref().remove(node)
+    }
+
+    pub fn contains(&self, node: &Entry<T>) -> bool {
+        self.nodes.contains(node)
+    }
+
+    // Add an edge from `from` to `to`. Returns false if it was there already
+    // or either end isn't a node of this graph.
+    pub fn add_edge(&self, from: &Entry<T>, to: &Entry<T>) -> bool {
+        self.contains(from) && self.contains(to) && self.edges.borrow_mut().insert(addr(from), addr(to))
+    }
+
+    pub fn remove_edge(&self, from: &Entry<T>, to: &Entry<T>) -> bool {
+        self.edges.borrow_mut().remove(addr(from), addr(to))
+    }
+
+    pub fn has_edge(&self, from: &Entry<T>, to: &Entry<T>) -> bool {
+        self.edges.borrow().contains(addr(from), addr(to))
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.borrow().len
+    }
+
+    // The node set itself stays private: its `on_remove` hook is what takes
+    // the edges out, and must not be replaced
+    pub fn for_each_node<F>(&self, f: F)
+    where F: FnMut(&T)
+    {
+        self.nodes.as_ref().for_each(f)
+    }
+
+    // Call `f` on the target of every edge from `node`. The edges are
+    // snapshotted first, and a target whose edge has gone by its turn (it
+    // dropped, say) is skipped, so `f` may change the graph.
+    pub fn for_each_successor<F>(&self, node: &Entry<T>, f: F)
+    where F: FnMut(Pin<&Entry<T>>)
+    {
+        let succs = self.edges.borrow().out.get(&addr(node)).map(|succs| succs.iter().copied().collect());
+        self.visit(succs.unwrap_or_default(), |succ| (addr(node), succ), f)
+    }
+
+    // Like `for_each_successor`, for the sources of edges into `node`
+    pub fn for_each_predecessor<F>(&self, node: &Entry<T>, f: F)
+    where F: FnMut(Pin<&Entry<T>>)
+    {
+        let preds = self.edges.borrow().into.get(&addr(node)).map(|preds| preds.iter().copied().collect());
+        self.visit(preds.unwrap_or_default(), |pred| (pred, addr(node)), f)
+    }
+
+    fn visit<E, F>(&self, others: Vec<*const ()>, edge: E, mut f: F)
+    where E: Fn(*const ()) -> (*const (), *const ()), F: FnMut(Pin<&Entry<T>>)
+    {
+        for other in others {
+            let (from, to) = edge(other);
+            if !self.edges.borrow().contains(from, to) {
+                continue;
+            }
+            // An edge only exists between live nodes
+            f(unsafe { Pin::new_unchecked(&*(other as *const Entry<T>)) });
+        }
+    }
+}
+
+impl<T: 'static> Default for WeakGraph<T> {
+    fn default() -> Self {
+        WeakGraph::new()
+    }
+}
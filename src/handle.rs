@@ -0,0 +1,83 @@
+// A Pin-free façade for application code. `WeakSetHandle` owns a pinned set
+// behind an `Rc` and can be cloned freely; `Member` owns a pinned entry and
+// leaves the set when dropped. Library code that needs control over where
+// entries live should keep using `WeakSet` and `Entry` directly.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::{Entry, WeakSet};
+
+pub struct WeakSetHandle<T> {
+    set: Rc<Pin<Box<WeakSet<T>>>>,
+}
+
+pub struct Member<T> {
+    entry: Pin<Box<Entry<T>>>,
+}
+
+impl<T> WeakSetHandle<T> {
+    pub fn new() -> Self {
+        WeakSetHandle { set: Rc::new(Box::pin(WeakSet::new())) }
+    }
+
+    pub fn insert(&self, x: T) -> Member<T> {
+        let entry = Box::pin(Entry::new(x));
+        self.as_pin().insert(entry.as_ref());
+        Member { entry }
+    }
+
+    // Take a member out of the set while keeping it alive
+    pub fn remove(&self, member: &Member<T>) -> bool {
+        self.as_pin().remove(member.entry.as_ref())
+    }
+
+    pub fn contains(&self, member: &Member<T>) -> bool {
+        member.entry.collection.get() == Some(NonNull::from(&self.set.header))
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    pub fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.as_pin().for_each(f)
+    }
+
+    pub fn as_pin(&self) -> Pin<&WeakSet<T>> {
+        (*self.set).as_ref()
+    }
+}
+
+impl<T> Clone for WeakSetHandle<T> {
+    fn clone(&self) -> Self {
+        WeakSetHandle { set: self.set.clone() }
+    }
+}
+
+impl<T> Default for WeakSetHandle<T> {
+    fn default() -> Self {
+        WeakSetHandle::new()
+    }
+}
+
+impl<T> Member<T> {
+    pub fn entry(&self) -> Pin<&Entry<T>> {
+        self.entry.as_ref()
+    }
+}
+
+impl<T> Deref for Member<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.entry
+    }
+}
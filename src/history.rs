@@ -0,0 +1,98 @@
+// A ring buffer of a set's recent membership changes, for finding out after
+// the fact who took a member out:
+//
+//     for record in set.history() {
+//         println!("{}", record);   // "#7 removed EntryId(..) at src/ui.rs:42:9"
+//     }
+//
+// Inserts and removals record where they were called from; entries that
+// drop have no caller to record. Removals made by `clear` and the other
+// closure-driven passes point into this crate rather than at their caller.
+
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::panic::Location;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::EntryId;
+
+const DEFAULT_CAPACITY: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Inserted,
+    Removed,
+    Dropped,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Record {
+    // Counts every change to the set, including those no longer kept
+    pub seq: u64,
+    #[cfg(feature = "std")]
+    pub at: Instant,
+    pub change: Change,
+    pub id: EntryId,
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = match self.change {
+            Change::Inserted => "inserted",
+            Change::Removed => "removed",
+            Change::Dropped => "dropped",
+        };
+        write!(f, "#{} {} {:?}", self.seq, change, self.id)?;
+        if let Some(location) = self.location {
+            write!(f, " at {}", location)?;
+        }
+        #[cfg(feature = "std")]
+        write!(f, " ({:?} ago)", self.at.elapsed())?;
+        Ok(())
+    }
+}
+
+pub(crate) struct History {
+    records: VecDeque<Record>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl History {
+    pub(crate) const fn new() -> Self {
+        History { records: VecDeque::new(), capacity: DEFAULT_CAPACITY, next_seq: 0 }
+    }
+
+    pub(crate) fn record(&mut self, change: Change, id: EntryId, location: Option<&'static Location<'static>>) {
+        if self.capacity == 0 {
+            self.next_seq += 1;
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(Record {
+            seq: self.next_seq,
+            #[cfg(feature = "std")]
+            at: Instant::now(),
+            change,
+            id,
+            location,
+        });
+        self.next_seq += 1;
+    }
+
+    pub(crate) fn records(&self) -> impl Iterator<Item = &Record> + '_ {
+        self.records.iter()
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        while self.records.len() > capacity {
+            self.records.pop_front();
+        }
+        self.records.shrink_to(capacity);
+        self.capacity = capacity;
+    }
+}
@@ -0,0 +1,132 @@
+// Interning with automatic collection. `intern` hands out `Interned` handles
+// that are shared by all equal values, and an interned value is dropped, and
+// forgotten by the interner, as soon as its last handle goes away: the
+// handles share one pinned entry, which leaves the interner's set on drop.
+//
+//     let names = WeakInterner::new();
+//     let a = names.intern("widget".to_string());
+//     let b = names.intern("widget".to_string());
+//     assert_eq!(a, b); // one allocation, compared by address
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::RefCell;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr;
+
+use hashbrown::{DefaultHashBuilder, HashTable};
+
+use crate::{Entry, WeakSet};
+
+pub struct WeakInterner<T> {
+    // Each value is kept with its hash, which the index uses rather than
+    // hashing values again
+    set: Pin<Box<WeakSet<(u64, T)>>>,
+    index: Rc<Index<T>>,
+}
+
+// The members by value. Shared with the set's `on_remove` hook, which takes
+// out the entries that drop.
+struct Index<T> {
+    table: RefCell<HashTable<*const Entry<(u64, T)>>>,
+    hasher: DefaultHashBuilder,
+}
+
+pub struct Interned<T> {
+    entry: Pin<Rc<Entry<(u64, T)>>>,
+}
+
+impl<T: Eq + Hash + 'static> WeakInterner<T> {
+    pub fn new() -> Self {
+        let index = Rc::new(Index { table: RefCell::new(HashTable::new()), hasher: DefaultHashBuilder::default() });
+        let set = Box::pin(WeakSet::new());
+        let hook = index.clone();
+        set.on_remove(move |member: &(u64, T)| {
+            let mut table = hook.table.borrow_mut();
+            if let Ok(found) = table.find_entry(member.0, |&entry| ptr::eq(unsafe { &(*entry).x }, member)) {
+                found.remove();
+            }
+        });
+        WeakInterner { set, index }
+    }
+
+    // The handle for `value`, shared with every other value equal to it
+    pub fn intern(&self, value: T) -> Interned<T> {
+        if let Some(interned) = self.get(&value) {
+            return interned;
+        }
+        let hash = self.index.hasher.hash_one(&value);
+        let entry = Rc::pin(Entry::new((hash, value)));
+        self.set.as_ref().insert(entry.as_ref());
+        self.index.table.borrow_mut().insert_unique(hash, &*entry as *const Entry<(u64, T)>, |&entry| {
+            unsafe { (*entry).x.0 }
+        });
+        Interned { entry }
+    }
+
+    // The handle for a value equal to `key`, if one is alive
+    pub fn get<Q>(&self, key: &Q) -> Option<Interned<T>>
+    where T: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        let hash = self.index.hasher.hash_one(key);
+        // `Eq` is user code that may drop handles, whose hook needs the
+        // table, so the candidates are taken out holding a handle each and
+        // compared after the table is released. Still in the table means
+        // still alive, so some handle owns an Rc to it that we can clone.
+        let candidates: Vec<_> = self.index.table.borrow().iter_hash(hash)
+            .filter(|&&entry| unsafe { (*entry).x.0 } == hash)
+            .map(|&entry| unsafe {
+                Rc::increment_strong_count(entry);
+                Interned { entry: Pin::new_unchecked(Rc::from_raw(entry)) }
+            })
+            .collect();
+        candidates.into_iter().find(|candidate| (**candidate).borrow() == key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+impl<T: Eq + Hash + 'static> Default for WeakInterner<T> {
+    fn default() -> Self {
+        WeakInterner::new()
+    }
+}
+
+impl<T> Clone for Interned<T> {
+    fn clone(&self) -> Self {
+        Interned { entry: self.entry.clone() }
+    }
+}
+
+impl<T> Deref for Interned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.entry.1
+    }
+}
+
+// Equal values share a handle, so comparing addresses is enough
+impl<T> PartialEq for Interned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(&*self.entry, &*other.entry)
+    }
+}
+
+impl<T> Eq for Interned<T> {}
+
+impl<T> Hash for Interned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(&*self.entry, state)
+    }
+}
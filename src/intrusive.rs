@@ -0,0 +1,104 @@
+// Adapters for the `intrusive-collections` crate, so one pinned allocation can
+// be a member of a `WeakSet` and of intrusive lists/trees at the same time.
+//
+// The payload carries the foreign link as a field and `entry_adapter!`
+// generates an adapter that maps between that link and the surrounding
+// `Entry`:
+//
+//     struct Node { link: LinkedListLink, value: u32 }
+//     weakset::entry_adapter!(NodeAdapter = Node { link: LinkedListLink });
+//
+//     let entry = Box::pin(Entry::new(Node { link: LinkedListLink::new(), value: 1 }));
+//     set.as_ref().insert(entry.as_ref());
+//     let mut list = LinkedList::new(NodeAdapter::new());
+//     list.push_back(unsafe { weakset::intrusive::unsafe_ref(entry.as_ref()) });
+//
+// The `WeakSet` still unlinks itself on drop, but foreign collections don't
+// know about pinning: the entry has to be taken out of them before it drops.
+
+use core::pin::Pin;
+
+use crate::Entry;
+
+#[doc(hidden)]
+pub use intrusive_collections as __ic;
+pub use intrusive_collections::UnsafeRef;
+
+// Wrap a pinned entry for insertion into an intrusive collection.
+//
+// Safety: the entry must be removed from every intrusive collection it is
+// inserted into before it is dropped.
+pub unsafe fn unsafe_ref<T>(entry: Pin<&Entry<T>>) -> UnsafeRef<Entry<T>> {
+    UnsafeRef::from_raw(&*entry)
+}
+
+#[doc(hidden)]
+pub fn payload_offset<T>() -> usize {
+    core::mem::offset_of!(Entry<T>, x)
+}
+
+// Define an intrusive-collections adapter for `Entry<$node>` whose link is
+// the `$field` field of the payload.
+#[macro_export]
+macro_rules! entry_adapter {
+    ($(#[$attr:meta])* $vis:vis $name:ident = $node:ty { $field:ident: $link:ty }) => {
+        $(#[$attr])*
+        $vis struct $name {
+            link_ops: <$link as $crate::intrusive::__ic::DefaultLinkOps>::Ops,
+            pointer_ops: $crate::intrusive::__ic::DefaultPointerOps<
+                $crate::intrusive::UnsafeRef<$crate::Entry<$node>>,
+            >,
+        }
+
+        #[allow(dead_code)]
+        impl $name {
+            pub const NEW: Self = $name {
+                link_ops: <$link as $crate::intrusive::__ic::DefaultLinkOps>::NEW,
+                pointer_ops: $crate::intrusive::__ic::DefaultPointerOps::new(),
+            };
+
+            pub fn new() -> Self {
+                Self::NEW
+            }
+
+            fn link_offset() -> usize {
+                $crate::intrusive::payload_offset::<$node>()
+                    + ::core::mem::offset_of!($node, $field)
+            }
+        }
+
+        unsafe impl $crate::intrusive::__ic::Adapter for $name {
+            type LinkOps = <$link as $crate::intrusive::__ic::DefaultLinkOps>::Ops;
+            type PointerOps = $crate::intrusive::__ic::DefaultPointerOps<
+                $crate::intrusive::UnsafeRef<$crate::Entry<$node>>,
+            >;
+
+            unsafe fn get_value(
+                &self,
+                link: <Self::LinkOps as $crate::intrusive::__ic::LinkOps>::LinkPtr,
+            ) -> *const $crate::Entry<$node> {
+                (link.as_ptr() as *const u8).sub(Self::link_offset()) as *const _
+            }
+
+            unsafe fn get_link(
+                &self,
+                value: *const $crate::Entry<$node>,
+            ) -> <Self::LinkOps as $crate::intrusive::__ic::LinkOps>::LinkPtr {
+                let link = (value as *const u8).add(Self::link_offset()) as *mut $link;
+                ::core::ptr::NonNull::new_unchecked(link)
+            }
+
+            fn link_ops(&self) -> &Self::LinkOps {
+                &self.link_ops
+            }
+
+            fn link_ops_mut(&mut self) -> &mut Self::LinkOps {
+                &mut self.link_ops
+            }
+
+            fn pointer_ops(&self) -> &Self::PointerOps {
+                &self.pointer_ops
+            }
+        }
+    };
+}
@@ -0,0 +1,201 @@
+// Lookup by a key the payload carries, on an otherwise plain `WeakSet`:
+//
+//     impl KeyedEntry for Connection {
+//         type Key = PeerId;
+//         fn key(&self) -> &PeerId { &self.peer }
+//     }
+//
+//     let set: WeakSet<Connection, KeyedStorage<Connection>> = WeakSet::new_keyed();
+//     let conn = set.as_ref().get_by_key(&peer);
+//
+// `KeyedStorage` is a `DenseStorage` plus a hash index reading each member's
+// key in place, so keys aren't copied and the index follows members that
+// drop. Keys must not change while registered. Members sharing a key are all
+// kept; `get_by_key` finds one of them.
+//
+// A key is hashed once, when its member is inserted, and the hash is kept
+// with the member: growing the index and removing members never call back
+// into `Hash`, so a key that changes anyway can at worst go missing from
+// lookups, never leave the index pointing at a member that is gone.
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+use hashbrown::{DefaultHashBuilder, HashTable};
+
+use crate::storage::{table_size, DenseIter, DenseStorage, Storage};
+use crate::{Entry, WeakSet};
+
+pub trait KeyedEntry {
+    type Key: Eq + Hash;
+
+    fn key(&self) -> &Self::Key;
+}
+
+pub struct KeyedStorage<T, M = ()> {
+    // Each member's hash is kept with its metadata and in the index
+    dense: DenseStorage<(u64, M)>,
+    index: HashTable<(u64, *const ())>,
+    hasher: DefaultHashBuilder,
+    // Hashed by `prepare_insert`, for the `insert` right after
+    pending: Cell<Option<(*const (), u64)>>,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T, M> Default for KeyedStorage<T, M> {
+    fn default() -> Self {
+        KeyedStorage {
+            dense: DenseStorage::new(),
+            index: HashTable::new(),
+            hasher: DefaultHashBuilder::default(),
+            pending: Cell::new(None),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: KeyedEntry, M> KeyedStorage<T, M> {
+    // Only called with members, which are live entries holding a `T`
+    fn key_of<'a>(entry: *const ()) -> &'a T::Key
+    where T: 'a
+    {
+        unsafe { (*(entry as *const Entry<T>)).x.key() }
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<*const ()>
+    where T::Key: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        let hash = self.hasher.hash_one(key);
+        self.index.find(hash, |&(_, entry)| Self::key_of(entry).borrow() == key).map(|&(_, entry)| entry)
+    }
+
+    fn hash_of(&self, entry: *const ()) -> u64 {
+        match self.pending.take() {
+            Some((pending, hash)) if pending == entry => hash,
+            _ => self.hasher.hash_one(Self::key_of(entry)),
+        }
+    }
+}
+
+pub struct KeyedIter<'a, M> {
+    base: DenseIter<'a, (u64, M)>,
+}
+
+impl<'a, M> Iterator for KeyedIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(entry, (_, meta))| (entry, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, M> DoubleEndedIterator for KeyedIter<'a, M> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.base.next_back().map(|(entry, (_, meta))| (entry, meta))
+    }
+}
+
+impl<'a, M> ExactSizeIterator for KeyedIter<'a, M> {}
+
+unsafe impl<T: KeyedEntry, M> Storage for KeyedStorage<T, M> {
+    type Meta = M;
+    type Iter<'a> = KeyedIter<'a, M>
+    where Self: 'a;
+
+    // A member already here keeps the hash it was inserted with
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        let hash = match self.dense.get(entry) {
+            Some(&(hash, _)) => {
+                self.pending.set(None);
+                hash
+            }
+            None => {
+                let hash = self.hash_of(entry);
+                self.index.insert_unique(hash, (hash, entry), |&(hash, _)| hash);
+                hash
+            }
+        };
+        self.dense.insert(entry, (hash, meta));
+    }
+
+    unsafe fn prepare_insert(&self, entry: *const ()) {
+        if !self.dense.contains(entry) {
+            self.pending.set(Some((entry, self.hasher.hash_one(Self::key_of(entry)))));
+        }
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        let (hash, meta) = self.dense.remove(entry)?;
+        if let Ok(found) = self.index.find_entry(hash, |&(_, member)| member == entry) {
+            found.remove();
+        }
+        Some(meta)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense.get(entry).map(|(_, meta)| meta)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense.get_mut(entry).map(|(_, meta)| meta)
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        KeyedIter { base: self.dense.iter() }
+    }
+
+    fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+        self.index.reserve(additional, |&(hash, _)| hash);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.dense.shrink_to(min);
+        self.index.shrink_to(min, |&(hash, _)| hash);
+    }
+
+    fn heap_size(&self) -> usize {
+        self.dense.heap_size() + table_size::<(u64, *const ())>(self.index.capacity())
+    }
+}
+
+impl<T: KeyedEntry, M> WeakSet<T, KeyedStorage<T, M>> {
+    pub fn new_keyed() -> Self {
+        WeakSet::with_storage(KeyedStorage::default())
+    }
+
+    // A copy of a member whose key equals `key`
+    pub fn get_by_key<Q>(self: Pin<&Self>, key: &Q) -> Option<T>
+    where T: Clone, T::Key: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        self.with_by_key(key, T::clone)
+    }
+
+    // Run `f` on a member whose key equals `key`. As in `for_each`, the
+    // member must not be dropped from inside `f`.
+    pub fn with_by_key<Q, R, F>(self: Pin<&Self>, key: &Q, f: F) -> Option<R>
+    where T::Key: Borrow<Q>, Q: Eq + Hash + ?Sized, F: FnOnce(&T) -> R
+    {
+        let objects = self.objects.borrow();
+        let entry = objects.get_by_key(key)?;
+        // Members are live entries, and stay so while the storage is borrowed
+        Some(f(unsafe { &(*(entry as *const Entry<T>)).x }))
+    }
+}
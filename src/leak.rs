@@ -0,0 +1,99 @@
+// Teardown checks for tests. Every set dropped with members still in it is
+// reported to a process-wide hook, and a count of the entries registered
+// anywhere right now catches registrations that outlive their scope even in
+// sets that never drop (statics, thread locals):
+//
+//     run_scenario();
+//     assert_eq!(weakset::leak::leaked(), 0);
+//     assert_eq!(weakset::leak::registered(), 0);
+//
+// These are process-wide, so tests relying on them shouldn't run alongside
+// others that register entries. Dropping a set with members is fine in
+// general (they're just detached), so the default hook only counts them;
+// `panic` and `log` are there to be set instead. Entries forgotten while
+// registered stay in their set, so they show up once that set drops.
+
+use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::EntryId;
+
+// A set dropped while not empty
+pub struct Leak<'a> {
+    // Where the set was, as in `raw::set_ptr`
+    pub set: *const (),
+    pub members: &'a [EntryId],
+}
+
+impl<'a> fmt::Debug for Leak<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "set at {:p} dropped with {} members: {:?}", self.set, self.members.len(), self.members)
+    }
+}
+
+pub type LeakHook = fn(&Leak<'_>);
+
+// The hook as a `usize`, 0 for the default
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+static LEAKED: AtomicUsize = AtomicUsize::new(0);
+static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_hook(hook: LeakHook) {
+    HOOK.store(hook as usize, Ordering::Relaxed);
+}
+
+// Go back to counting
+pub fn reset_hook() {
+    HOOK.store(0, Ordering::Relaxed);
+}
+
+pub fn panic(leak: &Leak<'_>) {
+    // A second panic during unwinding would abort, hiding the first
+    #[cfg(feature = "std")]
+    if std::thread::panicking() {
+        return;
+    }
+    panic!("{:?}", leak);
+}
+
+#[cfg(feature = "std")]
+pub fn log(leak: &Leak<'_>) {
+    std::eprintln!("weakset: {:?}", leak);
+}
+
+// Add the members to `leaked()`
+pub fn count(leak: &Leak<'_>) {
+    LEAKED.fetch_add(leak.members.len(), Ordering::Relaxed);
+}
+
+// Members reported to `count` so far
+pub fn leaked() -> usize {
+    LEAKED.load(Ordering::Relaxed)
+}
+
+pub fn reset_leaked() {
+    LEAKED.store(0, Ordering::Relaxed);
+}
+
+// Entries currently registered in some `WeakSet`
+pub fn registered() -> usize {
+    REGISTERED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn report(leak: &Leak<'_>) {
+    let hook = match HOOK.load(Ordering::Relaxed) {
+        0 => count as LeakHook,
+        // Only ever stored from a `LeakHook`
+        hook => unsafe { mem::transmute::<usize, LeakHook>(hook) },
+    };
+    hook(leak);
+}
+
+pub(crate) fn linked() {
+    REGISTERED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn unlinked(n: usize) {
+    REGISTERED.fetch_sub(n, Ordering::Relaxed);
+}
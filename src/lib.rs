@@ -0,0 +1,1300 @@
+// https://www.ralfj.de/blog/2018/04/10/safe-intrusive-collections-with-pinning.html
+
+// Without the `std` feature only `alloc` is needed
+#![cfg_attr(not(feature = "std"), no_std)]
+// Contracts of unsafe items are plain comments like everything else here: a
+// `Safety:` paragraph on the item itself
+#![allow(clippy::missing_safety_doc)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::Hash;
+use core::iter::FromIterator;
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem::{self, ManuallyDrop};
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::Waker;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver};
+
+use hashbrown::HashSet;
+
+use crate::cell::{StorageCell, StorageRef};
+
+// Instrumentation through `tracing`, compiled out (arguments included)
+// without the feature
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+// Enters a span for the rest of the enclosing block
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!($($arg)*).entered();
+    };
+}
+
+// Keeps the counts in `leak`, with the feature
+macro_rules! leak_check {
+    ($($call:tt)*) => {
+        #[cfg(feature = "leak-check")]
+        crate::leak::$($call)*;
+    };
+}
+
+// Adds to the set's history, with the feature. Without a location the
+// caller's is recorded.
+macro_rules! record {
+    ($set:expr, $change:ident, $id:expr) => {
+        record!($set, $change, $id, Some(core::panic::Location::caller()))
+    };
+    ($set:expr, $change:ident, $id:expr, $location:expr) => {
+        #[cfg(feature = "history")]
+        $set.history.borrow_mut().record(crate::history::Change::$change, $id, $location);
+    };
+}
+
+#[cfg(feature = "allocator-api2")]
+pub mod allocator;
+mod algebra;
+mod any;
+mod arena;
+mod builder;
+#[cfg(feature = "std")]
+mod bus;
+mod cell;
+#[cfg(feature = "cxx")]
+pub mod cpp;
+#[cfg(feature = "critical-section")]
+mod critical;
+mod detach;
+mod dot;
+mod frozen;
+#[cfg(feature = "bumpalo")]
+pub mod bump;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "heapless")]
+pub mod fixed;
+mod graph;
+mod handle;
+#[cfg(feature = "history")]
+pub mod history;
+mod intern;
+mod macros;
+mod map;
+#[cfg(feature = "intrusive-collections")]
+pub mod intrusive;
+mod keyed;
+#[cfg(feature = "leak-check")]
+pub mod leak;
+mod observable;
+mod pool;
+mod query;
+pub mod raw;
+mod signal;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod storage;
+#[cfg(all(feature = "std", feature = "futures-core"))]
+mod stream;
+mod strong;
+mod waker;
+mod weighted;
+
+pub use algebra::SetDiff;
+pub use any::AnyWeakSet;
+pub use arena::{Arena, ArenaWeakSet};
+pub use builder::WeakSetBuilder;
+#[cfg(feature = "std")]
+pub use bus::EventBus;
+#[cfg(feature = "critical-section")]
+pub use critical::CriticalWeakSet;
+pub use detach::Detached;
+pub use dot::DotGraph;
+pub use frozen::FrozenWeakSet;
+pub use graph::WeakGraph;
+pub use handle::{Member, WeakSetHandle};
+pub use intern::{Interned, WeakInterner};
+pub use keyed::{KeyedEntry, KeyedIter, KeyedStorage};
+pub use map::{MapHandle, WeakMap};
+pub use observable::Observable;
+pub use pool::{EntryPool, PoolKey, Pooled};
+pub use signal::{Batch, Connection, Signal, Slot, SlotEntry};
+pub use storage::{
+    DenseStorage, HashStorage, InlineStorage, OpenStorage, PriorityStorage, PtrBuildHasher, SortedStorage,
+    Storage,
+};
+#[cfg(all(feature = "std", feature = "futures-core"))]
+pub use stream::{ChangesStream, Lagged};
+pub use strong::{StrongKey, StrongSet};
+pub use waker::{Waiter, WakerSet};
+pub use weighted::{WeightedIter, WeightedStorage};
+
+// For the exported macros, which can't name `alloc` without std
+#[doc(hidden)]
+pub use alloc::boxed::Box as __Box;
+
+// What an entry points back to. Every collection starts with a header, so an
+// entry can detach itself on drop without knowing the collection's storage.
+pub(crate) struct Header<T> {
+    unlink: unsafe fn(*const Header<T>, *const Entry<T>),
+}
+
+#[repr(C)]
+pub struct WeakSet<T, S: Storage = DenseStorage> {
+    // must stay the first field, entries point here
+    header: Header<T>,
+    objects: StorageCell<S>,
+    // Snapshots of the members for each `broadcast` in progress, innermost
+    // last. Members that drop mid-dispatch are nulled out here.
+    dispatches: RefCell<Vec<Dispatch>>,
+    // Snapshot buffers of finished broadcasts, reused by the next ones so
+    // that dispatching doesn't allocate once the set has settled
+    spare: RefCell<Vec<Vec<*const ()>>>,
+    // Members added with `insert_once`, removed after their first broadcast
+    once: RefCell<HashSet<*const (), PtrBuildHasher>>,
+    auto_shrink: Cell<bool>,
+    stats: Cell<Stats>,
+    on_insert: Cell<Option<Hook<T>>>,
+    on_remove: Cell<Option<Hook<T>>>,
+    subscribers: RefCell<Vec<Subscriber>>,
+    #[cfg(feature = "history")]
+    history: RefCell<history::History>,
+    _p: PhantomPinned,
+}
+
+type Hook<T> = Box<dyn FnMut(&T)>;
+// Delivers a membership event, returning false once nobody is listening. A
+// subscriber that needs a task woken leaves its waker in the `Vec`, to be
+// woken once the subscribers are released.
+type Subscriber = Box<dyn FnMut(MembershipEvent, &mut Vec<Waker>) -> bool>;
+
+// Identifies an entry while it is alive. Ids are derived from the entry's
+// address, so like file descriptors they can be reused once it is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntryId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MembershipEvent {
+    Inserted(EntryId),
+    Removed(EntryId),
+}
+
+// Heap memory held by a set, in bytes. The hooks' closures aren't counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    // the member table
+    pub storage: usize,
+    // snapshots kept for broadcasts in progress
+    pub dispatch: usize,
+    // `insert_once` marks and change subscribers
+    pub bookkeeping: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.storage + self.dispatch + self.bookkeeping
+    }
+}
+
+// Counters kept over the set's lifetime
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub inserts: usize,
+    // taken out by `remove`, `clear` and the like
+    pub removals: usize,
+    // taken out by dropping the entry
+    pub drop_removals: usize,
+    // most members at any one time
+    pub peak_len: usize,
+    // times the storage moved its members to a new table
+    pub rehashes: usize,
+}
+
+struct Dispatch {
+    members: Vec<*const ()>,
+    // the member whose callback is running
+    visiting: *const (),
+}
+
+type FinalDrop<T> = Box<dyn FnOnce(Pin<&mut T>)>;
+type OnDetached = Box<dyn FnOnce()>;
+
+// Kept out of line since most entries never set any
+struct Hooks<T> {
+    // run once when the entry itself is dropped
+    final_drop: Option<FinalDrop<T>>,
+    // run the next time the entry leaves a collection
+    detached: Option<OnDetached>,
+}
+
+#[repr(C)]
+pub struct Entry<T> {
+    // Must stay the first field: storages may keep the entry's position here
+    // to find it without a lookup (see `DenseStorage`)
+    slot: Cell<usize>,
+    x: T,
+    // set to Some if we are part of some collection
+    collection: Cell<Option<NonNull<Header<T>>>>,
+    hooks: Cell<Option<Box<Hooks<T>>>>,
+    _p: PhantomPinned,
+}
+
+// Entries get embedded in lots of small objects, so keep them at three words
+const _: () = assert!(mem::size_of::<Entry<()>>() == 3 * mem::size_of::<usize>());
+
+// An entry with no payload, for owners that only need to show they're still
+// alive and registered. The payload takes no space, so a token is just the
+// link words above.
+pub type PresenceToken = Entry<()>;
+
+// Only used inside the crate: the items borrow the set rather than the
+// guard, so one kept past the end of the iteration could dangle once its
+// entry drops. Users get the closure visitors (`for_each` and friends).
+pub(crate) struct Iter<'a, K: 'a, S: Storage + 'a = DenseStorage> {
+    base: S::Iter<'a>,
+    // Keeps the set borrowed so entries can't unlink themselves while we
+    // walk the storage. Declared after `base` so it is released last.
+    _guard: StorageRef<'a, S>,
+    _marker: PhantomData<&'a Entry<K>>,
+}
+
+impl<'a, K, S: Storage> Iterator for Iter<'a, K, S> {
+    type Item = &'a Entry<K>;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a Entry<K>> {
+        self.base.next().map(|(x, _)| unsafe { &*(x as *const Entry<K>) })
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, K, S: Storage> DoubleEndedIterator for Iter<'a, K, S>
+where S::Iter<'a>: DoubleEndedIterator
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a Entry<K>> {
+        self.base.next_back().map(|(x, _)| unsafe { &*(x as *const Entry<K>) })
+    }
+}
+
+impl<'a, K, S: Storage> ExactSizeIterator for Iter<'a, K, S>
+where S::Iter<'a>: ExactSizeIterator {}
+
+// Like `Iter`, but also yields each member's metadata
+pub(crate) struct MetaIter<'a, K: 'a, S: Storage + 'a = DenseStorage> {
+    base: S::Iter<'a>,
+    _guard: StorageRef<'a, S>,
+    _marker: PhantomData<&'a Entry<K>>,
+}
+
+impl<'a, K, S: Storage> Iterator for MetaIter<'a, K, S> {
+    type Item = (&'a K, &'a S::Meta);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(x, meta)| (unsafe { &(*(x as *const Entry<K>)).x }, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<T> WeakSet<T> {
+    // Allocates nothing until the first insert, so types can embed sets
+    // that usually stay empty for free
+    pub const fn new() -> Self {
+        WeakSet::with_storage(DenseStorage::new())
+    }
+}
+
+impl<T, S: Storage> WeakSet<T, S> {
+    pub const fn with_storage(storage: S) -> Self {
+        WeakSet {
+            header: Header { unlink: Self::unlink },
+            objects: StorageCell::new(storage),
+            dispatches: RefCell::new(Vec::new()),
+            spare: RefCell::new(Vec::new()),
+            once: RefCell::new(HashSet::with_hasher(PtrBuildHasher::new())),
+            auto_shrink: Cell::new(true),
+            stats: Cell::new(Stats { inserts: 0, removals: 0, drop_removals: 0, peak_len: 0, rehashes: 0 }),
+            on_insert: Cell::new(None),
+            on_remove: Cell::new(None),
+            subscribers: RefCell::new(Vec::new()),
+            #[cfg(feature = "history")]
+            history: RefCell::new(history::History::new()),
+            _p: PhantomPinned,
+        }
+    }
+
+    unsafe fn unlink(header: *const Header<T>, entry: *const Entry<T>) {
+        let this = &*(header as *const Self);
+        let entry = entry as *const ();
+        if this.objects.is_borrowed() {
+            // The entry's memory goes away whatever we do here, and a panic
+            // would leave the storage pointing at it
+            abort("member dropped while its set is being iterated");
+        }
+        this.skip_in_dispatches(entry, true);
+        this.once.borrow_mut().remove(&entry);
+        let meta = this.objects.borrow_mut().remove(entry);
+        leak_check!(unlinked(1));
+        if meta.is_none() {
+            // Already taken out by a batch removal that hasn't reached it
+            // yet, and has counted and recorded it; it skips the entry now,
+            // so only the notification is left
+            this.removed(&*(entry as *const Entry<T>));
+            return;
+        }
+        record!(this, Dropped, EntryId(entry as usize), None);
+        this.count(|stats| stats.drop_removals += 1);
+        trace!(len = this.len(), "member dropped");
+        // dropped outside the borrow in case it touches the set
+        drop(meta);
+        this.maybe_shrink();
+        this.removed(&*(entry as *const Entry<T>));
+    }
+
+    // Call `f` with the payload of every entry that leaves the set, whether
+    // it was dropped or taken out by `remove` or `clear`, once the set is
+    // consistent again. Dropping the set itself doesn't call it. Removals
+    // made by `f` itself don't call `f` again.
+    pub fn on_remove<F>(&self, f: F)
+    where F: FnMut(&T) + 'static
+    {
+        self.on_remove.set(Some(Box::new(f)));
+    }
+
+    // Call `f` with the payload of every entry added to the set, right after
+    // it has been registered. Insertions made by `f` itself don't call `f`
+    // again.
+    pub fn on_insert<F>(&self, f: F)
+    where F: FnMut(&T) + 'static
+    {
+        self.on_insert.set(Some(Box::new(f)));
+    }
+
+    // Get a channel of membership changes, e.g. to mirror the set's contents
+    // on another thread. The channel disconnects when the set is dropped.
+    #[cfg(feature = "std")]
+    pub fn subscribe_changes(&self) -> Receiver<MembershipEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribe(Box::new(move |event, _| sender.send(event).is_ok()));
+        receiver
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn subscribe(&self, subscriber: Subscriber) {
+        self.subscribers.borrow_mut().push(subscriber);
+    }
+
+    fn notify(&self, event: MembershipEvent) {
+        let mut wakers = Vec::new();
+        // forget subscribers that hung up
+        self.subscribers.borrow_mut().retain_mut(|subscriber| subscriber(event, &mut wakers));
+        // a woken task may poll right away, and use the set
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    fn inserted(&self, entry: &Entry<T>) {
+        self.notify(MembershipEvent::Inserted(entry.id()));
+        Self::fire(&self.on_insert, &entry.x);
+    }
+
+    fn removed(&self, entry: &Entry<T>) {
+        self.notify(MembershipEvent::Removed(entry.id()));
+        Self::fire(&self.on_remove, &entry.x);
+    }
+
+    fn fire(slot: &Cell<Option<Hook<T>>>, x: &T) {
+        // Puts the hook back afterwards, also if it panics
+        struct Restore<'a, T> {
+            slot: &'a Cell<Option<Hook<T>>>,
+            hook: Option<Hook<T>>,
+        }
+
+        impl<'a, T> Drop for Restore<'a, T> {
+            fn drop(&mut self) {
+                // unless the hook replaced itself
+                let hook = self.slot.take().or_else(|| self.hook.take());
+                self.slot.set(hook);
+            }
+        }
+
+        let mut restore = Restore { slot, hook: slot.take() };
+        if let Some(hook) = restore.hook.as_mut() {
+            hook(x);
+        }
+    }
+
+    // Make broadcasts in progress skip `entry` if they haven't reached it yet
+    fn skip_in_dispatches(&self, entry: *const (), dropping: bool) {
+        for dispatch in self.dispatches.borrow_mut().iter_mut() {
+            if dropping && dispatch.visiting == entry {
+                // The callback still holds a reference to this entry's
+                // payload, and there is no way to keep the memory alive.
+                abort("entry dropped from inside its own broadcast callback");
+            }
+            for member in dispatch.members.iter_mut().filter(|member| **member == entry) {
+                *member = ptr::null();
+            }
+        }
+    }
+
+    // Add the entry to the collection. The membership table sits behind a
+    // `RefCell`, so a shared pinned reference is enough; this lets a set
+    // shared through an `Rc` gain members while other code holds it.
+    //
+    // The returned id can take the member out again with `remove_by_id`,
+    // for callers that can't hold on to the entry reference.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> EntryId
+    where S::Meta: Default
+    {
+        self.insert_with_meta(entry, S::Meta::default())
+    }
+
+    // Add the entry for a single broadcast: it is taken out of the set again
+    // right after its first broadcast callback returns
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert_once(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> EntryId
+    where S::Meta: Default
+    {
+        let id = self.insert(entry);
+        self.mark_once(entry);
+        id
+    }
+
+    // Make `entry` a member only while `f` runs, e.g. a progress reporter
+    // for the duration of a job. It is taken out again afterwards, also when
+    // `f` panics.
+    pub fn with_member<R, F>(self: Pin<&Self>, entry: Pin<&Entry<T>>, f: F) -> R
+    where S::Meta: Default, F: FnOnce() -> R
+    {
+        self.insert(entry);
+        let _leave = LeaveGuard { set: self, entry };
+        f()
+    }
+
+    pub(crate) fn mark_once(self: Pin<&Self>, entry: Pin<&Entry<T>>) {
+        self.once.borrow_mut().insert(&*entry as *const Entry<T> as *const ());
+    }
+
+    // Add the entry along with a metadata value kept by the set
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert_with_meta(self: Pin<&Self>, entry: Pin<&Entry<T>>, meta: S::Meta) -> EntryId {
+        self.link(entry, |objects, ptr| unsafe { objects.insert(ptr, meta) });
+        entry.id()
+    }
+
+    // Link `entry` to this set, letting `store` put it in the storage
+    #[cfg_attr(feature = "history", track_caller)]
+    pub(crate) fn link<F>(self: Pin<&Self>, entry: Pin<&Entry<T>>, store: F)
+    where F: FnOnce(&mut S, *const ())
+    {
+        if entry.collection.get().is_some() {
+            panic!("Can't insert the same object into multiple collections");
+        }
+        // Pointer from collection to entry
+        let this : &Self = self.get_ref();
+        let ptr = &*entry as *const Entry<T> as *const ();
+        // `ptr` is a pinned entry, and it stays alive while it's a member
+        // since dropping it unlinks it; the same goes for `store`
+        unsafe { this.objects.borrow().prepare_insert(ptr) };
+        store(&mut this.objects.borrow_mut(), ptr);
+        let len = this.len();
+        this.count(|stats| {
+            stats.inserts += 1;
+            stats.peak_len = stats.peak_len.max(len);
+        });
+        trace!(len, "insert");
+        // Pointer from entry to collection
+        entry.collection.set(Some(NonNull::from(&this.header)));
+        leak_check!(linked());
+        record!(this, Inserted, entry.id());
+        this.inserted(&entry);
+    }
+
+    // A copy of the metadata the set keeps for `entry`
+    pub fn meta(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> Option<S::Meta>
+    where S::Meta: Clone
+    {
+        unsafe { self.objects.borrow().get(&*entry as *const Entry<T> as *const ()) }.cloned()
+    }
+
+    // Replace the metadata for `entry`, returning the old value
+    pub fn set_meta(self: Pin<&Self>, entry: Pin<&Entry<T>>, meta: S::Meta) -> Option<S::Meta> {
+        let mut objects = self.objects.borrow_mut();
+        let slot = unsafe { objects.get_mut(&*entry as *const Entry<T> as *const ()) }?;
+        Some(core::mem::replace(slot, meta))
+    }
+
+    // Take the entry out of the collection. Returns false if it wasn't a
+    // member of this one.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn remove(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> bool {
+        match self.take(entry) {
+            Some(meta) => {
+                drop(meta);
+                self.finish_removal(&entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Take out the member with this id, if it is still in the set. Ids are
+    // addresses, so once a member is dropped its id may come to name a later
+    // entry allocated at the same place.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn remove_by_id(self: Pin<&Self>, id: EntryId) -> bool {
+        let ptr = id.0 as *const ();
+        if !self.objects.borrow().contains_addr(ptr) {
+            return false;
+        }
+        // Members are live entries
+        self.remove(unsafe { Pin::new_unchecked(&*(ptr as *const Entry<T>)) })
+    }
+
+    // Unlink `entry` and hand back its metadata, leaving the notifications
+    // to the caller
+    #[cfg_attr(feature = "history", track_caller)]
+    fn take(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> Option<S::Meta> {
+        if entry.collection.get() != Some(NonNull::from(&self.header)) {
+            return None;
+        }
+        let ptr = &*entry as *const Entry<T> as *const ();
+        self.skip_in_dispatches(ptr, false);
+        self.once.borrow_mut().remove(&ptr);
+        let meta = unsafe { self.objects.borrow_mut().remove(ptr) };
+        self.count(|stats| stats.removals += 1);
+        trace!(len = self.len(), "remove");
+        entry.collection.set(None);
+        leak_check!(unlinked(1));
+        record!(self, Removed, entry.id());
+        self.maybe_shrink();
+        meta
+    }
+
+    // Notify about an entry that has just been taken out. Hooks may drop the
+    // entry, so it isn't touched after they start.
+    fn finish_removal(&self, entry: &Entry<T>) {
+        let detached = entry.take_hook(|hooks| hooks.detached.take());
+        self.removed(entry);
+        if let Some(f) = detached {
+            f();
+        }
+    }
+
+    // Take several entries out of the collection at once, returning how many
+    // were members. The storage is only borrowed once for the lot.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn remove_all<'e, I>(self: Pin<&Self>, entries: I) -> usize
+    where I: IntoIterator<Item = Pin<&'e Entry<T>>>, T: 'e
+    {
+        let header = Some(NonNull::from(&self.header));
+        let members = entries.into_iter()
+            .filter(|entry| entry.collection.get() == header)
+            .map(|entry| &*entry as *const Entry<T> as *const ())
+            .collect();
+        self.remove_batch(members)
+    }
+
+    // Take out every member whose payload matches `pred`, returning how many
+    // there were. `pred` runs with the set borrowed, so it must not insert
+    // or drop members.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn clear_matching<F>(self: Pin<&Self>, mut pred: F) -> usize
+    where F: FnMut(&T) -> bool
+    {
+        let members = self.iter()
+            .filter(|entry| pred(&entry.x))
+            .map(|entry| entry as *const Entry<T> as *const ())
+            .collect();
+        self.remove_batch(members)
+    }
+
+    // Keep the first member (in iteration order) for every key and take the
+    // others out, returning how many were removed
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn dedup_by_key<K: Eq + Hash, F>(self: Pin<&Self>, mut key: F) -> usize
+    where F: FnMut(&T) -> K
+    {
+        let mut seen = HashSet::new();
+        let members = self.iter()
+            .filter(|entry| !seen.insert(key(&entry.x)))
+            .map(|entry| entry as *const Entry<T> as *const ())
+            .collect();
+        self.remove_batch(members)
+    }
+
+    // Move the members matching `pred`, metadata included, into a new set.
+    // They count as removed from this one, so its `on_remove` hook and the
+    // entries' `on_detached` hooks run, each once the entry is in the new
+    // set.
+    pub fn split_off<F>(self: Pin<&Self>, mut pred: F) -> Pin<Box<Self>>
+    where S: Default, F: FnMut(&T) -> bool
+    {
+        let split = Box::pin(WeakSet::with_storage(S::default()));
+        let members = self.iter()
+            .filter(|entry| pred(&entry.x))
+            .map(|entry| entry as *const Entry<T> as *const ())
+            .collect();
+        self.dispatch_over(members, |entry| {
+            if let Some(meta) = self.take(entry) {
+                split.as_ref().insert_with_meta(entry, meta);
+                self.finish_removal(&entry);
+            }
+        });
+        split
+    }
+
+    #[cfg_attr(feature = "history", track_caller)]
+    fn remove_batch(self: Pin<&Self>, members: Vec<*const ()>) -> usize {
+        let mut metas = Vec::with_capacity(members.len());
+        let mut removed = Vec::with_capacity(members.len());
+        {
+            let mut objects = self.objects.borrow_mut();
+            let mut once = self.once.borrow_mut();
+            // skipping duplicates
+            for member in members {
+                // members are live until they are unlinked
+                if let Some(meta) = unsafe { objects.remove(member) } {
+                    once.remove(&member);
+                    metas.push(meta);
+                    removed.push(member);
+                }
+            }
+        }
+        for &member in &removed {
+            self.skip_in_dispatches(member, false);
+            record!(self, Removed, EntryId(member as usize));
+        }
+        let count = removed.len();
+        self.count(|stats| stats.removals += count);
+        trace!(removed = count, len = self.len(), "remove batch");
+        // The back-pointers stay until each entry's turn, so an entry dropped
+        // by an earlier entry's hooks still unlinks itself (and is skipped)
+        self.dispatch_over(removed, |entry| {
+            entry.collection.set(None);
+            leak_check!(unlinked(1));
+            self.finish_removal(&entry);
+        });
+        drop(metas);
+        self.maybe_shrink();
+        count
+    }
+
+    // Take every entry out of the collection. Entries added by an
+    // `on_remove` hook meanwhile stay in.
+    pub fn clear(self: Pin<&Self>) {
+        self.dispatch_over(self.snapshot(), |entry| {
+            self.remove(entry);
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.borrow().len()
+    }
+
+    // Whether `entry` is a member of this set. Only looks at the entry, so
+    // the storage isn't touched.
+    pub fn contains(&self, entry: &Entry<T>) -> bool {
+        entry.collection.get() == Some(NonNull::from(&self.header))
+    }
+
+    // By default the storage gives memory back once it is less than a
+    // quarter full, shrinking to twice the remaining size so that churn
+    // around the threshold doesn't reallocate every time. Latency-critical
+    // users can turn that off and call `shrink_to_fit` at a time that suits
+    // them.
+    pub fn set_auto_shrink(&self, on: bool) {
+        self.auto_shrink.set(on);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.objects.borrow().capacity()
+    }
+
+    // Make room for `additional` more members up front. With auto-shrink on
+    // the room is given back again once few of it is used.
+    pub fn reserve(&self, additional: usize) {
+        self.objects.borrow_mut().reserve(additional);
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats { rehashes: self.objects.borrow().rehashes(), ..self.stats.get() }
+    }
+
+    // The most recent membership changes, oldest first
+    #[cfg(feature = "history")]
+    pub fn history(&self) -> Vec<history::Record> {
+        self.history.borrow().records().copied().collect()
+    }
+
+    // Write the history one change per line
+    #[cfg(feature = "history")]
+    pub fn dump_history<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        for record in self.history.borrow().records() {
+            writeln!(w, "{}", record)?;
+        }
+        Ok(())
+    }
+
+    // How many changes the history keeps (64 to begin with); 0 turns it off
+    #[cfg(feature = "history")]
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.history.borrow_mut().set_capacity(capacity);
+    }
+
+    fn count<F: FnOnce(&mut Stats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let dispatches = self.dispatches.borrow();
+        let dispatch = dispatches.capacity() * mem::size_of::<Dispatch>()
+            + dispatches.iter().map(|d| d.members.capacity() * mem::size_of::<*const ()>()).sum::<usize>()
+            + self.spare.borrow().iter().map(|members| members.capacity() * mem::size_of::<*const ()>()).sum::<usize>();
+        let once = self.once.borrow().capacity() * (mem::size_of::<*const ()>() + 1);
+        let subscribers = self.subscribers.borrow().capacity() * mem::size_of::<Subscriber>();
+        MemoryUsage { storage: self.objects.borrow().heap_size(), dispatch, bookkeeping: once + subscribers }
+    }
+
+    // Also drops the buffers kept around for broadcasts
+    pub fn shrink_to_fit(&self) {
+        *self.spare.borrow_mut() = Vec::new();
+        let mut objects = self.objects.borrow_mut();
+        let len = objects.len();
+        objects.shrink_to(len);
+    }
+
+    fn maybe_shrink(&self) {
+        const MIN_CAPACITY: usize = 64;
+        if !self.auto_shrink.get() {
+            return;
+        }
+        let mut objects = self.objects.borrow_mut();
+        let (len, capacity) = (objects.len(), objects.capacity());
+        if capacity > MIN_CAPACITY && len * 4 < capacity {
+            objects.shrink_to(len * 2);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Call `f` on every member. The set stays borrowed throughout, so `f`
+    // must not insert or drop members: inserting panics, and dropping a
+    // member aborts the process.
+    pub fn for_each<F>(self: Pin<&Self>, mut f: F)
+    where F: FnMut(&T)
+    {
+        for entry in self.iter() {
+            f(&entry.x);
+        }
+    }
+
+    pub(crate) fn iter(self: Pin<&Self>) -> Iter<'_, T, S> {
+        let guard = self.get_ref().borrow_for_iter();
+        // The storage lives inside the pinned set and can't be mutated while
+        // `guard` is alive, so extending the borrow to the guard's lifetime
+        // is fine.
+        let storage: &S = unsafe { &*(&*guard as *const S) };
+        Iter { base: storage.iter(), _guard: guard, _marker: PhantomData }
+    }
+
+    pub(crate) fn iter_meta(self: Pin<&Self>) -> MetaIter<'_, T, S> {
+        let guard = self.get_ref().borrow_for_iter();
+        let storage: &S = unsafe { &*(&*guard as *const S) };
+        MetaIter { base: storage.iter(), _guard: guard, _marker: PhantomData }
+    }
+
+    // Call `f` on every member and its metadata, with the same restrictions
+    // as `for_each`
+    pub fn for_each_meta<F>(self: Pin<&Self>, mut f: F)
+    where F: FnMut(&T, &S::Meta)
+    {
+        for (x, meta) in self.iter_meta() {
+            f(x, meta);
+        }
+    }
+
+    // Call `f` on every member, exactly once each, without keeping the set
+    // borrowed while `f` runs. Membership is snapshotted first: members added
+    // by a callback are left for the next broadcast, and members dropped by a
+    // callback before their turn are skipped. A callback must not drop the
+    // member it was called for (that aborts the process); taking it out with
+    // `remove` is fine.
+    pub fn broadcast<F>(self: Pin<&Self>, mut f: F)
+    where F: FnMut(&T)
+    {
+        self.broadcast_pinned(|x| f(x.get_ref()))
+    }
+
+    pub fn broadcast_pinned<F>(self: Pin<&Self>, mut f: F)
+    where F: FnMut(Pin<&T>)
+    {
+        self.dispatch(|entry| f(entry.get_pin()))
+    }
+
+    // Like `broadcast`, visiting the members in the order given by `cmp`,
+    // e.g. to draw widgets in z-order. The order is worked out up front with
+    // the set borrowed, so `cmp` must not add or remove members.
+    pub fn for_each_sorted_by<C, F>(self: Pin<&Self>, mut cmp: C, mut f: F)
+    where C: FnMut(&T, &T) -> Ordering, F: FnMut(&T)
+    {
+        let mut members = self.snapshot();
+        {
+            let _guard = self.borrow_for_iter();
+            let x = |member: &*const ()| unsafe { &(*(*member as *const Entry<T>)).x };
+            members.sort_by(|a, b| cmp(x(a), x(b)));
+        }
+        self.dispatch_over(members, |entry| f(&entry.x));
+    }
+
+    pub fn for_each_sorted_by_key<K: Ord, G, F>(self: Pin<&Self>, mut key: G, f: F)
+    where G: FnMut(&T) -> K, F: FnMut(&T)
+    {
+        self.for_each_sorted_by(|a, b| key(a).cmp(&key(b)), f)
+    }
+
+    // Like `broadcast`, but a panicking callback doesn't stop the others.
+    // Returns the ids of the members whose callback panicked.
+    #[cfg(feature = "std")]
+    pub fn broadcast_isolated<F>(self: Pin<&Self>, mut f: F) -> Vec<EntryId>
+    where F: FnMut(&T)
+    {
+        let mut panicked = Vec::new();
+        self.dispatch(|entry| {
+            if panic::catch_unwind(AssertUnwindSafe(|| f(&entry.x))).is_err() {
+                panicked.push(entry.id());
+            }
+        });
+        panicked
+    }
+
+    // Visit a snapshot of the members, taking out the `insert_once` ones
+    // after their turn
+    fn dispatch<F>(self: Pin<&Self>, mut f: F)
+    where F: FnMut(Pin<&Entry<T>>)
+    {
+        self.dispatch_over(self.snapshot(), |entry| {
+            let ptr = &*entry as *const Entry<T> as *const ();
+            f(entry);
+            // Still marked means still a member, so still alive
+            if self.once.borrow().contains(&ptr) {
+                self.remove(unsafe { Pin::new_unchecked(&*(ptr as *const Entry<T>)) });
+            }
+        })
+    }
+
+    fn snapshot(&self) -> Vec<*const ()> {
+        let mut members = self.spare.borrow_mut().pop().unwrap_or_default();
+        members.extend(self.borrow_for_iter().iter().map(|(entry, _)| entry));
+        members
+    }
+
+    // Let the storage get ready for a pass over the members, unless it is
+    // being iterated already
+    fn borrow_for_iter(&self) -> StorageRef<'_, S> {
+        if let Some(mut objects) = self.objects.try_borrow_mut() {
+            objects.before_iter();
+        }
+        let objects = self.objects.borrow();
+        trace!(len = objects.len(), "iterate");
+        objects
+    }
+
+    fn dispatch_over<F>(self: Pin<&Self>, members: Vec<*const ()>, mut f: F)
+    where F: FnMut(Pin<&Entry<T>>)
+    {
+        if members.is_empty() {
+            recycle(&self.spare, members);
+            return;
+        }
+        trace_span!("broadcast", members = members.len());
+        let mut dispatches = self.dispatches.borrow_mut();
+        let depth = dispatches.len();
+        dispatches.push(Dispatch { members, visiting: ptr::null() });
+        drop(dispatches);
+        let _guard = DispatchGuard { dispatches: &self.dispatches, spare: &self.spare };
+
+        let mut i = 0;
+        loop {
+            let next = {
+                let mut dispatches = self.dispatches.borrow_mut();
+                let dispatch = &mut dispatches[depth];
+                match dispatch.members.get(i) {
+                    Some(&member) => {
+                        dispatch.visiting = member;
+                        member
+                    }
+                    None => break,
+                }
+            };
+            i += 1;
+            if !next.is_null() {
+                f(unsafe { Pin::new_unchecked(&*(next as *const Entry<T>)) });
+            }
+            self.dispatches.borrow_mut()[depth].visiting = ptr::null();
+        }
+    }
+
+    // Call `f` on a clone of every member, with the same snapshot rules as
+    // `broadcast`. Each member is cloned just before its turn and `f` only
+    // sees the clone, so a callback may also drop the member it was called
+    // for. When `T` is a strong handle (`Rc<_>`, `Arc<_>`) this keeps the
+    // current listener alive until its callback returns.
+    pub fn for_each_strong<F>(self: Pin<&Self>, mut f: F)
+    where T: Clone, F: FnMut(&T)
+    {
+        self.dispatch_strong(|_, x| f(x))
+    }
+
+    // Copy the current contents. The membership itself can't be cloned (an
+    // entry is in at most one set), so this clones the payloads into an
+    // owned collection instead, e.g. a `Vec<T>` or a `StrongSet<T>` whose
+    // entries can then be registered elsewhere.
+    pub fn clone_values<C: FromIterator<T>>(self: Pin<&Self>) -> C
+    where T: Clone
+    {
+        self.iter().map(|entry| entry.x.clone()).collect()
+    }
+
+    pub(crate) fn dispatch_strong<F>(self: Pin<&Self>, mut f: F)
+    where T: Clone, F: FnMut(EntryId, &T)
+    {
+        self.dispatch(|entry| {
+            let (id, x) = (entry.id(), entry.x.clone());
+            // Nested dispatches have been popped by now, so ours is last.
+            // Nothing borrows the entry past this point.
+            self.dispatches.borrow_mut().last_mut().unwrap().visiting = ptr::null();
+            f(id, &x)
+        })
+    }
+
+    // Write all entries of the collection as "[ 42, 43, ]"
+    pub fn dump<W: fmt::Write>(self: Pin<&Self>, w: &mut W) -> fmt::Result
+    where T: fmt::Debug
+    {
+        w.write_str("[")?;
+        for entry in self.iter() {
+            write!(w, " {:?},", entry.x)?;
+        }
+        w.write_str(" ]")
+    }
+
+    // For payloads that aren't `Debug`: the member count and each member's
+    // address
+    pub fn debug_addresses(&self) -> impl fmt::Debug + '_ {
+        DebugAddresses { set: self }
+    }
+
+    // The entries as a list, for `{:?}` and `{:#?}`
+    pub fn debug_entries(self: Pin<&Self>) -> impl fmt::Debug + '_
+    where T: fmt::Debug
+    {
+        DebugEntries { set: self }
+    }
+}
+
+struct DebugEntries<'a, T, S: Storage> {
+    set: Pin<&'a WeakSet<T, S>>,
+}
+
+impl<'a, T: fmt::Debug, S: Storage> fmt::Debug for DebugEntries<'a, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.set.iter().map(|entry| &entry.x)).finish()
+    }
+}
+
+struct DebugAddresses<'a, T, S: Storage> {
+    set: &'a WeakSet<T, S>,
+}
+
+impl<'a, T, S: Storage> fmt::Debug for DebugAddresses<'a, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let objects = self.set.borrow_for_iter();
+        f.debug_struct("WeakSet").field("len", &objects.len()).field("members", &Addresses(&*objects)).finish()
+    }
+}
+
+struct Addresses<'a, S>(&'a S);
+
+impl<'a, S: Storage> fmt::Debug for Addresses<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter().map(|(x, _)| x)).finish()
+    }
+}
+
+// Lists the payloads. Only needs `&self`: a set that was never pinned has
+// no members to walk.
+impl<T: fmt::Debug, S: Storage> fmt::Debug for WeakSet<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let objects = self.borrow_for_iter();
+        f.debug_set().entries(objects.iter().map(|(x, _)| unsafe { &(*(x as *const Entry<T>)).x })).finish()
+    }
+}
+
+// Takes the entry of `with_member` out again, also when its closure panics
+struct LeaveGuard<'a, T, S: Storage> {
+    set: Pin<&'a WeakSet<T, S>>,
+    entry: Pin<&'a Entry<T>>,
+}
+
+impl<'a, T, S: Storage> Drop for LeaveGuard<'a, T, S> {
+    fn drop(&mut self) {
+        self.set.remove(self.entry);
+    }
+}
+
+// Pops the innermost dispatch, also when a callback panics
+struct DispatchGuard<'a> {
+    dispatches: &'a RefCell<Vec<Dispatch>>,
+    spare: &'a RefCell<Vec<Vec<*const ()>>>,
+}
+
+impl<'a> Drop for DispatchGuard<'a> {
+    fn drop(&mut self) {
+        let dispatch = self.dispatches.borrow_mut().pop();
+        if let Some(dispatch) = dispatch {
+            recycle(self.spare, dispatch.members);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn abort(msg: &str) -> ! {
+    eprintln!("weakset: {}", msg);
+    std::process::abort()
+}
+
+// Without std there's no `abort`, but panicking while a panic unwinds aborts
+#[cfg(not(feature = "std"))]
+fn abort(msg: &str) -> ! {
+    struct Abort;
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("weakset: aborting");
+        }
+    }
+    let _abort = Abort;
+    panic!("weakset: {}", msg)
+}
+
+// Keep a snapshot buffer for the next broadcast. There are never more
+// spares than the deepest nesting of broadcasts so far.
+fn recycle(spare: &RefCell<Vec<Vec<*const ()>>>, mut members: Vec<*const ()>) {
+    if members.capacity() > 0 {
+        members.clear();
+        spare.borrow_mut().push(members);
+    }
+}
+
+impl<T, S: Storage + Default> Default for WeakSet<T, S> {
+    fn default() -> Self {
+        WeakSet::with_storage(S::default())
+    }
+}
+
+impl<T, S: Storage> Drop for WeakSet<T, S> {
+    fn drop(&mut self) {
+        // Go through the entries to remove pointers to collection
+        let mut detached = Vec::new();
+        #[cfg(feature = "leak-check")]
+        let mut leaked = Vec::new();
+        for (entry, _) in self.objects.get_mut().iter() {
+            let entry : &Entry<T> = unsafe { &*(entry as *const Entry<T>) };
+            entry.collection.set(None);
+            detached.extend(entry.take_hook(|hooks| hooks.detached.take()));
+            #[cfg(feature = "leak-check")]
+            leaked.push(entry.id());
+        }
+        // Only run once no entry points here anymore, since they may drop
+        // entries
+        for f in detached {
+            f();
+        }
+        #[cfg(feature = "leak-check")]
+        if !leaked.is_empty() {
+            leak::unlinked(leaked.len());
+            leak::report(&leak::Leak { set: &self.header as *const Header<T> as *const (), members: &leaked });
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    pub fn new(x: T) -> Self {
+        Entry {
+            slot: Cell::new(0),
+            x,
+            collection: Cell::new(None),
+            hooks: Cell::new(None),
+            _p: PhantomPinned,
+        }
+    }
+
+    // A new boxed entry holding `x`, already a member of `set`. Only takes
+    // a shared pin, like `insert`.
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn new_in<S: Storage>(set: Pin<&WeakSet<T, S>>, x: T) -> Pin<Box<Self>>
+    where S::Meta: Default
+    {
+        let entry = Box::pin(Entry::new(x));
+        set.insert(entry.as_ref());
+        entry
+    }
+
+    pub fn id(&self) -> EntryId {
+        EntryId(self as *const Self as usize)
+    }
+
+    pub fn is_registered(&self) -> bool {
+        guarded(|| self.collection.get().is_some())
+    }
+
+    // The payload is structurally pinned: it never moves while the entry is
+    // pinned, and is dropped in place.
+    pub fn get_pin(self: Pin<&Self>) -> Pin<&T> {
+        unsafe { self.map_unchecked(|entry| &entry.x) }
+    }
+
+    // Reuse a detached entry for a new payload without giving up its pinned
+    // allocation. The old payload is dropped in place, and any
+    // `on_final_drop` hook is discarded along with it. The payload must not
+    // be linked into foreign intrusive collections at this point.
+    pub fn reset(self: Pin<&mut Self>, x: T) {
+        if self.is_registered() {
+            panic!("Can't reset an entry that is part of a collection");
+        }
+        let this = unsafe { Pin::get_unchecked_mut(self) };
+        drop(this.take_hook(|hooks| hooks.final_drop.take()));
+        // Writes the new payload once the old one is gone, even if dropping
+        // the old one panics
+        struct Refill<T>(*mut T, ManuallyDrop<T>);
+        impl<T> Drop for Refill<T> {
+            fn drop(&mut self) {
+                unsafe { ptr::write(self.0, ManuallyDrop::take(&mut self.1)) }
+            }
+        }
+        let refill = Refill(&mut this.x, ManuallyDrop::new(x));
+        unsafe { ptr::drop_in_place(refill.0) };
+    }
+
+    // Run `f` when this entry is finally dropped, after it has left its
+    // collection. For a `Pin<Arc<Entry<T>>>` that is when the last clone goes
+    // away, which makes it the place for end-of-life cleanup. Setting a new
+    // hook replaces the previous one without running it.
+    pub fn on_final_drop<F>(&self, f: F)
+    where F: FnOnce(Pin<&mut T>) + 'static
+    {
+        self.set_hooks(|hooks| hooks.final_drop = Some(Box::new(f)));
+    }
+
+    // Take one hook out, freeing the hooks once none is left
+    fn take_hook<H>(&self, pick: impl FnOnce(&mut Hooks<T>) -> Option<H>) -> Option<H> {
+        guarded(|| {
+            let mut hooks = self.hooks.take()?;
+            let hook = pick(&mut hooks);
+            if hooks.final_drop.is_some() || hooks.detached.is_some() {
+                self.hooks.set(Some(hooks));
+            }
+            hook
+        })
+    }
+
+    fn set_hooks(&self, f: impl FnOnce(&mut Hooks<T>)) {
+        guarded(|| {
+            let mut hooks = self.hooks.take()
+                .unwrap_or_else(|| Box::new(Hooks { final_drop: None, detached: None }));
+            f(&mut hooks);
+            self.hooks.set(Some(hooks));
+        })
+    }
+}
+
+// An entry may belong to a `CriticalWeakSet` that an interrupt clears, which
+// writes the entry's link and takes its hooks, so the entry's own accesses to
+// those go through a critical section as well
+#[cfg(feature = "critical-section")]
+fn guarded<R>(f: impl FnOnce() -> R) -> R {
+    critical_section::with(|_| f())
+}
+
+#[cfg(not(feature = "critical-section"))]
+#[inline(always)]
+fn guarded<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+impl<T> Deref for Entry<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.x
+    }
+}
+
+impl Default for PresenceToken {
+    fn default() -> Self {
+        Entry::new(())
+    }
+}
+
+impl<S: Storage> WeakSet<(), S> {
+    // A new boxed token, already registered here
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn new_token(self: Pin<&Self>) -> Pin<Box<PresenceToken>>
+    where S::Meta: Default
+    {
+        Entry::new_in(self, ())
+    }
+}
+
+impl<T> Drop for Entry<T> {
+    fn drop(&mut self) {
+        // Go through collection to remove this entry. Checking the link and
+        // unlinking happen in one critical section, or a concurrent `clear`
+        // could detach the entry in between.
+        let linked = guarded(|| match self.collection.get() {
+            Some(collection) => {
+                unsafe { (collection.as_ref().unlink)(collection.as_ptr(), self) };
+                true
+            }
+            None => false,
+        });
+        if linked {
+            self.fire_detached();
+        }
+        if let Some(f) = self.take_hook(|hooks| hooks.final_drop.take()) {
+            f(unsafe { Pin::new_unchecked(&mut self.x) });
+        }
+    }
+}
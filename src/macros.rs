@@ -0,0 +1,53 @@
+// Shorthands for building populated registries, mostly for examples and
+// tests:
+//
+//     let a = pinned_entry!(1);
+//     let b = pinned_entry!(2);
+//     let set = weakset![a, b];
+//     let c = pinned_entry!(3, in set);
+//
+// The entries are only borrowed, so they stay owned by their bindings and
+// leave the set when those go out of scope.
+
+// A new `Pin<Box<WeakSet<_>>>`, with the given pinned entries (`Pin<Box<_>>`,
+// `Pin<Rc<_>>`, `Pin<&_>`, ...) inserted
+#[macro_export]
+macro_rules! weakset {
+    () => {
+        $crate::__Box::pin($crate::WeakSet::new())
+    };
+    ($($entry:expr),+ $(,)?) => {{
+        let set = $crate::__Box::pin($crate::WeakSet::new());
+        $(set.as_ref().insert(::core::pin::Pin::as_ref(&$entry));)+
+        set
+    }};
+}
+
+// A new `Pin<Box<Entry<_>>>` holding `x`, optionally inserted into `set`
+#[macro_export]
+macro_rules! pinned_entry {
+    ($x:expr) => {
+        $crate::__Box::pin($crate::Entry::new($x))
+    };
+    ($x:expr, in $set:expr) => {
+        $crate::Entry::new_in(::core::pin::Pin::as_ref(&$set), $x)
+    };
+}
+
+// Stack-pinned entries, one binding each, for short scopes that shouldn't
+// allocate:
+//
+//     pin_entries!(a = 1, b = 2);
+//     set.as_ref().insert(a);
+//
+// Each name is bound to a `Pin<&Entry<_>>` whose entry lives until the end
+// of the enclosing block.
+#[macro_export]
+macro_rules! pin_entries {
+    ($($name:ident = $x:expr),+ $(,)?) => {
+        $(
+            let $name = ::core::pin::pin!($crate::Entry::new($x));
+            let $name = $name.into_ref();
+        )+
+    };
+}
@@ -0,0 +1,154 @@
+// A cache of live objects by key. The map hands out shared `MapHandle`s and
+// doesn't keep values alive itself: once the last handle to a value goes
+// away its entry drops, leaves the map's set, and the key is forgotten.
+//
+//     let sessions = WeakMap::new();
+//     let a = sessions.get_or_insert_with(user, || Session::open(user));
+//     let b = sessions.get_or_insert_with(user, || Session::open(user));
+//     // `b` is `a` again as long as `a` is alive
+//
+// Built like `WeakInterner`, with the index looking at the key part of each
+// member.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::RefCell;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr;
+
+use hashbrown::{DefaultHashBuilder, HashTable};
+
+use crate::{Entry, WeakSet};
+
+// A key's hash, the key and its value
+type Member<K, V> = (u64, K, V);
+
+pub struct WeakMap<K, V> {
+    // Members carry their key's hash, as in `WeakInterner`
+    set: Pin<Box<WeakSet<Member<K, V>>>>,
+    index: Rc<Index<K, V>>,
+}
+
+// The members by key, shared with the set's `on_remove` hook
+struct Index<K, V> {
+    table: RefCell<HashTable<*const Entry<Member<K, V>>>>,
+    hasher: DefaultHashBuilder,
+}
+
+pub struct MapHandle<K, V> {
+    entry: Pin<Rc<Entry<Member<K, V>>>>,
+}
+
+impl<K: Eq + Hash + 'static, V: 'static> WeakMap<K, V> {
+    pub fn new() -> Self {
+        let index = Rc::new(Index { table: RefCell::new(HashTable::new()), hasher: DefaultHashBuilder::default() });
+        let set = Box::pin(WeakSet::new());
+        let hook = index.clone();
+        set.on_remove(move |member: &Member<K, V>| {
+            let mut table = hook.table.borrow_mut();
+            if let Ok(found) = table.find_entry(member.0, |&entry| ptr::eq(unsafe { &(*entry).x }, member)) {
+                found.remove();
+            }
+        });
+        WeakMap { set, index }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<MapHandle<K, V>>
+    where K: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        self.find(self.index.hasher.hash_one(key), key)
+    }
+
+    // The live value for `key`, or a new one made by `f` and registered
+    // under it. `f` runs with the index released, so it may use the map; if
+    // it registered `key` itself, that value wins and `f`'s is dropped.
+    pub fn get_or_insert_with<F>(&self, key: K, f: F) -> MapHandle<K, V>
+    where F: FnOnce() -> V
+    {
+        let hash = self.index.hasher.hash_one(&key);
+        if let Some(found) = self.find(hash, &key) {
+            return found;
+        }
+        let value = f();
+        if let Some(found) = self.find(hash, &key) {
+            return found;
+        }
+        let entry = Rc::pin(Entry::new((hash, key, value)));
+        self.set.as_ref().insert(entry.as_ref());
+        self.index.table.borrow_mut().insert_unique(hash, &*entry as *const Entry<Member<K, V>>, |&entry| {
+            unsafe { (*entry).x.0 }
+        });
+        MapHandle { entry }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where K: Borrow<Q>, Q: Eq + Hash + ?Sized
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    // Keys are compared with the table released, as in `WeakInterner::get`
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<MapHandle<K, V>>
+    where K: Borrow<Q>, Q: Eq + ?Sized
+    {
+        let candidates: Vec<_> = self.index.table.borrow().iter_hash(hash)
+            .filter(|&&entry| unsafe { (*entry).x.0 } == hash)
+            .map(|&entry| unsafe { MapHandle::from_member(entry) })
+            .collect();
+        candidates.into_iter().find(|candidate| candidate.key().borrow() == key)
+    }
+}
+
+impl<K: Eq + Hash + 'static, V: 'static> Default for WeakMap<K, V> {
+    fn default() -> Self {
+        WeakMap::new()
+    }
+}
+
+impl<K, V> MapHandle<K, V> {
+    // Still in the index means still alive, so some handle owns an Rc to it
+    // that we can clone
+    unsafe fn from_member(entry: *const Entry<Member<K, V>>) -> Self {
+        Rc::increment_strong_count(entry);
+        MapHandle { entry: Pin::new_unchecked(Rc::from_raw(entry)) }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.entry.1
+    }
+}
+
+impl<K, V> Clone for MapHandle<K, V> {
+    fn clone(&self) -> Self {
+        MapHandle { entry: self.entry.clone() }
+    }
+}
+
+impl<K, V> Deref for MapHandle<K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.entry.2
+    }
+}
+
+// Handles for the same key while it's alive share one entry
+impl<K, V> PartialEq for MapHandle<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(&*self.entry, &*other.entry)
+    }
+}
+
+impl<K, V> Eq for MapHandle<K, V> {}
@@ -0,0 +1,52 @@
+// A value paired with a signal that fires whenever the value changes: the
+// basic building block for data binding. Listeners unsubscribe by dropping
+// their `Connection`.
+
+use core::cell::{Ref, RefCell};
+
+use crate::{Connection, Signal, Slot};
+
+pub struct Observable<T> {
+    value: RefCell<T>,
+    changed: Signal<T>,
+}
+
+impl<T> Observable<T> {
+    pub fn new(value: T) -> Self {
+        Observable { value: RefCell::new(value), changed: Signal::new() }
+    }
+
+    pub fn get(&self) -> Ref<'_, T> {
+        self.value.borrow()
+    }
+
+    // Listeners are called with the new value. They may read it but must not
+    // change it from inside the notification.
+    pub fn subscribe<S: Slot<T> + 'static>(&self, listener: S) -> Connection<T> {
+        self.changed.connect(listener)
+    }
+
+    pub fn set(&self, value: T) {
+        drop(self.value.replace(value));
+        self.notify();
+    }
+
+    pub fn update<F: FnOnce(&mut T)>(&self, f: F) {
+        f(&mut self.value.borrow_mut());
+        self.notify();
+    }
+
+    fn notify(&self) {
+        self.changed.emit(&self.value.borrow());
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for Observable<T> {
+    fn default() -> Self {
+        Observable::new(T::default())
+    }
+}
@@ -0,0 +1,164 @@
+// A slab of entries for registries with high membership turnover. Slots live
+// in fixed-size chunks that never move, so an entry is pinned in place for as
+// long as its `Pooled` handle is alive, and dropping the handle (which unlinks
+// the entry) just hands the slot back for the next `alloc`.
+//
+// Every slot carries a generation that is bumped on both alloc and release,
+// odd while occupied, so a `PoolKey` for a recycled slot is detectably stale.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::pin::Pin;
+
+use crate::Entry;
+
+const CHUNK: usize = 64;
+
+struct Slot<T> {
+    generation: Cell<u32>,
+    entry: UnsafeCell<MaybeUninit<Entry<T>>>,
+}
+
+impl<T> Slot<T> {
+    fn occupied(&self) -> bool {
+        self.generation.get() % 2 == 1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    index: usize,
+    generation: u32,
+}
+
+pub struct EntryPool<T> {
+    chunks: RefCell<Vec<Box<[Slot<T>]>>>,
+    free: RefCell<Vec<usize>>,
+}
+
+impl<T> EntryPool<T> {
+    pub fn new() -> Self {
+        EntryPool { chunks: RefCell::new(Vec::new()), free: RefCell::new(Vec::new()) }
+    }
+
+    // Number of slots, occupied or not
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().len() * CHUNK
+    }
+
+    pub fn len(&self) -> usize {
+        self.capacity() - self.free.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, index: usize) -> &Slot<T> {
+        let chunks = self.chunks.borrow();
+        let slot: *const Slot<T> = &chunks[index / CHUNK][index % CHUNK];
+        // Chunks are boxed and only freed when the pool drops
+        unsafe { &*slot }
+    }
+
+    pub fn alloc(&self, x: T) -> Pooled<'_, T> {
+        let reused = self.free.borrow_mut().pop();
+        let index = match reused {
+            Some(index) => index,
+            None => {
+                let mut chunks = self.chunks.borrow_mut();
+                let start = chunks.len() * CHUNK;
+                chunks.push((0..CHUNK).map(|_| Slot {
+                    generation: Cell::new(0),
+                    entry: UnsafeCell::new(MaybeUninit::uninit()),
+                }).collect());
+                // hand out the first slot, keep the rest, lowest index last
+                self.free.borrow_mut().extend((start + 1..start + CHUNK).rev());
+                start
+            }
+        };
+        let slot = self.slot(index);
+        unsafe { (*slot.entry.get()).write(Entry::new(x)) };
+        slot.generation.set(slot.generation.get().wrapping_add(1));
+        Pooled { pool: self, index }
+    }
+
+    // Whether the entry `key` was issued for is still alive
+    pub fn contains(&self, key: PoolKey) -> bool {
+        key.index < self.capacity() && self.slot(key.index).generation.get() == key.generation
+    }
+
+    fn release(&self, index: usize) {
+        // The slot is free once the entry's drop starts, even if it panics
+        // (the payload's drop or a hook), or the pool would drop it again
+        struct Free<'a, T> {
+            pool: &'a EntryPool<T>,
+            index: usize,
+        }
+
+        impl<'a, T> Drop for Free<'a, T> {
+            fn drop(&mut self) {
+                let slot = self.pool.slot(self.index);
+                slot.generation.set(slot.generation.get().wrapping_add(1));
+                self.pool.free.borrow_mut().push(self.index);
+            }
+        }
+
+        let slot = self.slot(index);
+        let _free = Free { pool: self, index };
+        // Unlinks the entry from its set
+        unsafe { (*slot.entry.get()).assume_init_drop() };
+    }
+}
+
+impl<T> Default for EntryPool<T> {
+    fn default() -> Self {
+        EntryPool::new()
+    }
+}
+
+impl<T> Drop for EntryPool<T> {
+    fn drop(&mut self) {
+        // Handles borrow the pool, so anything still occupied was leaked with
+        // `mem::forget`. It was pinned, so it must be dropped before its
+        // memory goes away.
+        for chunk in self.chunks.get_mut().iter() {
+            for slot in chunk.iter().filter(|slot| slot.occupied()) {
+                unsafe { (*slot.entry.get()).assume_init_drop() };
+            }
+        }
+    }
+}
+
+// An entry checked out of an `EntryPool`; dropping it returns the slot
+pub struct Pooled<'p, T> {
+    pool: &'p EntryPool<T>,
+    index: usize,
+}
+
+impl<'p, T> Pooled<'p, T> {
+    pub fn key(&self) -> PoolKey {
+        PoolKey { index: self.index, generation: self.pool.slot(self.index).generation.get() }
+    }
+
+    pub fn as_pin(&self) -> Pin<&Entry<T>> {
+        unsafe { Pin::new_unchecked(&**self) }
+    }
+}
+
+impl<'p, T> Deref for Pooled<'p, T> {
+    type Target = Entry<T>;
+
+    fn deref(&self) -> &Entry<T> {
+        unsafe { (*self.pool.slot(self.index).entry.get()).assume_init_ref() }
+    }
+}
+
+impl<'p, T> Drop for Pooled<'p, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
@@ -0,0 +1,113 @@
+// One-shot queries over the current members. Each runs under a single
+// borrow of the storage, so the predicates must not add or remove members.
+// Members come back as clones or ids, never as references: those would
+// outlive the borrow, and dangle once their entry drops.
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::pin::Pin;
+
+use hashbrown::HashMap;
+
+use crate::{EntryId, Storage, WeakSet};
+
+impl<T, S: Storage> WeakSet<T, S> {
+    // Clones of the members matching `pred`, and of the others
+    pub fn partition<F>(self: Pin<&Self>, mut pred: F) -> (Vec<T>, Vec<T>)
+    where T: Clone, F: FnMut(&T) -> bool
+    {
+        self.iter().map(|entry| entry.x.clone()).partition(|x| pred(x))
+    }
+
+    // Like `partition`, without cloning: the ids of the members, e.g. for
+    // `remove_by_id`
+    pub fn partition_ids<F>(self: Pin<&Self>, mut pred: F) -> (Vec<EntryId>, Vec<EntryId>)
+    where F: FnMut(&T) -> bool
+    {
+        let (mut matching, mut rest) = (Vec::new(), Vec::new());
+        for entry in self.iter() {
+            if pred(&entry.x) { &mut matching } else { &mut rest }.push(entry.id());
+        }
+        (matching, rest)
+    }
+
+    // How many members match `pred`, without allocating
+    pub fn count_matching<F>(self: Pin<&Self>, mut pred: F) -> usize
+    where F: FnMut(&T) -> bool
+    {
+        self.iter().filter(|entry| pred(&entry.x)).count()
+    }
+
+    // A clone of the first member in iteration order matching `pred`
+    pub fn find<F>(self: Pin<&Self>, mut pred: F) -> Option<T>
+    where T: Clone, F: FnMut(&T) -> bool
+    {
+        self.iter().map(|entry| &entry.x).find(|x| pred(x)).cloned()
+    }
+
+    pub fn any<F>(self: Pin<&Self>, mut pred: F) -> bool
+    where F: FnMut(&T) -> bool
+    {
+        self.iter().any(|entry| pred(&entry.x))
+    }
+
+    // True for an empty set
+    pub fn all<F>(self: Pin<&Self>, mut pred: F) -> bool
+    where F: FnMut(&T) -> bool
+    {
+        self.iter().all(|entry| pred(&entry.x))
+    }
+
+    pub fn fold<B, F>(self: Pin<&Self>, init: B, mut f: F) -> B
+    where F: FnMut(B, &T) -> B
+    {
+        self.iter().fold(init, |acc, entry| f(acc, &entry.x))
+    }
+
+    // Like `fold`, starting from a clone of the first member. `None` for an
+    // empty set.
+    pub fn reduce<F>(self: Pin<&Self>, mut f: F) -> Option<T>
+    where T: Clone, F: FnMut(T, &T) -> T
+    {
+        let mut members = self.iter();
+        let first = members.next()?.x.clone();
+        Some(members.fold(first, |acc, entry| f(acc, &entry.x)))
+    }
+
+    // A clone of the member with the smallest key, the first one on ties
+    pub fn min_by_key<K: Ord, F>(self: Pin<&Self>, mut key: F) -> Option<T>
+    where T: Clone, F: FnMut(&T) -> K
+    {
+        self.iter().map(|entry| &entry.x).min_by_key(|x| key(x)).cloned()
+    }
+
+    // A clone of the member with the largest key, the last one on ties
+    pub fn max_by_key<K: Ord, F>(self: Pin<&Self>, mut key: F) -> Option<T>
+    where T: Clone, F: FnMut(&T) -> K
+    {
+        self.iter().map(|entry| &entry.x).max_by_key(|x| key(x)).cloned()
+    }
+
+    // The ids of the members bucketed by `key`, each bucket in iteration
+    // order
+    pub fn group_ids_by<K: Eq + Hash, F>(self: Pin<&Self>, mut key: F) -> HashMap<K, Vec<EntryId>>
+    where F: FnMut(&T) -> K
+    {
+        let mut groups: HashMap<K, Vec<EntryId>> = HashMap::new();
+        for entry in self.iter() {
+            groups.entry(key(&entry.x)).or_default().push(entry.id());
+        }
+        groups
+    }
+
+    // Like `group_ids_by`, with clones of the members
+    pub fn group_by_cloned<K: Eq + Hash, F>(self: Pin<&Self>, mut key: F) -> HashMap<K, Vec<T>>
+    where T: Clone, F: FnMut(&T) -> K
+    {
+        let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+        for entry in self.iter() {
+            groups.entry(key(&entry.x)).or_default().push(entry.x.clone());
+        }
+        groups
+    }
+}
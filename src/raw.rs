@@ -0,0 +1,118 @@
+// Direct access to a set's storage and to the links between sets and their
+// entries, for building specialized collections on top of `WeakSet` (the
+// graph and keyed storages in this crate could have been). Nothing here runs
+// hooks, notifies subscribers or counts stats.
+//
+// The set's own code relies on these invariants, so every `unsafe` function
+// below requires them to hold again by the time it returns (or the closure
+// passed to it does):
+//
+// - a pointer is in a set's storage exactly when the entry it points to is
+//   linked to that set
+// - every stored pointer is the address (see `entry_ptr`) of a live, pinned
+//   `Entry<T>` for the set's own `T`
+// - an entry is linked to at most one set, and that set stays where it is
+//   for as long as the link exists, since dropping the entry unlinks it
+//   through it
+//
+// Closures given mutable access to a storage must not touch the set or any
+// of its members in other ways, and in particular must not drop a member.
+
+use core::mem;
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::storage::Storage;
+use crate::{Entry, WeakSet};
+
+// The address a set stores for `entry`
+pub fn entry_ptr<T>(entry: &Entry<T>) -> *const () {
+    entry as *const Entry<T> as *const ()
+}
+
+// Turn a stored pointer back into an entry.
+//
+// Safety: `ptr` must point at a live `Entry<T>` that stays pinned for `'a`.
+pub unsafe fn entry_ref<'a, T>(ptr: *const ()) -> Pin<&'a Entry<T>> {
+    Pin::new_unchecked(&*(ptr as *const Entry<T>))
+}
+
+// The entry holding the payload at `x`, as seen e.g. by an `on_remove` hook.
+// Only computes the address; it's up to the caller to know that `x` really is
+// a payload.
+pub fn entry_of_payload<T>(x: *const T) -> *const () {
+    (x as *const u8).wrapping_sub(mem::offset_of!(Entry<T>, x)) as *const ()
+}
+
+// The address of the set `entry` is linked to, if any, comparable to
+// `set_ptr`
+pub fn linked_set<T>(entry: &Entry<T>) -> Option<*const ()> {
+    entry.collection.get().map(|header| header.as_ptr() as *const ())
+}
+
+pub fn set_ptr<T, S: Storage>(set: &WeakSet<T, S>) -> *const () {
+    &set.header as *const _ as *const ()
+}
+
+// Run `f` on the storage. The set can't be changed until `f` returns: a
+// member dropping in the meantime aborts, as it would during an iteration.
+pub fn with_storage<T, S: Storage, R, F>(set: &WeakSet<T, S>, f: F) -> R
+where F: FnOnce(&S) -> R
+{
+    f(&set.objects.borrow())
+}
+
+// Run `f` on the storage mutably, e.g. to reorder members or change their
+// metadata in bulk. Members `f` takes out or puts in aren't linked or
+// unlinked; see `link` and `unlink` for that.
+//
+// Safety: the module's invariants must hold once `f` returns, and `f` must
+// not touch the set or its members otherwise. Pointers removed while a
+// broadcast is in progress may still be visited by it.
+pub unsafe fn with_storage_mut<T, S: Storage, R, F>(set: &WeakSet<T, S>, f: F) -> R
+where F: FnOnce(&mut S) -> R
+{
+    f(&mut set.objects.borrow_mut())
+}
+
+// Point `entry` at `set` (or at nothing) without touching any storage.
+//
+// Safety: the module's invariants must hold again before the entry drops or
+// the set is used, e.g. by adding the entry to the storage of its new set
+// and taking it out of the old one's.
+pub unsafe fn set_link<T, S: Storage>(entry: Pin<&Entry<T>>, set: Option<Pin<&WeakSet<T, S>>>) {
+    let link = set.map(|set| NonNull::from(&set.get_ref().header));
+    #[cfg(feature = "leak-check")]
+    match (entry.collection.get().is_some(), link.is_some()) {
+        (false, true) => crate::leak::linked(),
+        (true, false) => crate::leak::unlinked(1),
+        _ => {}
+    }
+    entry.collection.set(link);
+}
+
+// Store `entry` in the set and link it, without the hooks and bookkeeping of
+// `insert`.
+//
+// Safety: `entry` must not be linked to any set.
+pub unsafe fn link<T, S: Storage>(set: Pin<&WeakSet<T, S>>, entry: Pin<&Entry<T>>, meta: S::Meta) {
+    set.objects.borrow().prepare_insert(entry_ptr(&entry));
+    set.objects.borrow_mut().insert(entry_ptr(&entry), meta);
+    set_link(entry, Some(set));
+}
+
+// Take `entry` out of the set and unlink it, without the hooks of `remove`.
+// Broadcasts in progress skip it, as after a `remove`. Returns `None`, and
+// does nothing, if it isn't linked to this set.
+pub fn unlink<T, S: Storage>(set: Pin<&WeakSet<T, S>>, entry: Pin<&Entry<T>>) -> Option<S::Meta> {
+    if linked_set(&entry) != Some(set_ptr(&set)) {
+        return None;
+    }
+    let ptr = entry_ptr(&entry);
+    set.skip_in_dispatches(ptr, false);
+    set.once.borrow_mut().remove(&ptr);
+    let meta = unsafe { set.objects.borrow_mut().remove(ptr) };
+    entry.collection.set(None);
+    leak_check!(unlinked(1));
+    meta
+}
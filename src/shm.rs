@@ -0,0 +1,313 @@
+// An experimental registry that lives entirely inside a shared-memory
+// segment, for processes that share objects and want to know which of them
+// are still around. Each process maps the segment at whatever address it
+// gets, so nothing in it is a pointer: the member table holds entry offsets
+// from the start of the segment, and each entry holds its index in the table
+// (the same back-index `DenseStorage` keeps in `Entry::slot`).
+//
+// Entries are allocated from a pool in the segment, and the `ShmEntry` handle
+// owns one the way a pinned `Entry` owns its membership: dropping the handle
+// takes the entry out of the registry and returns it to the pool. A process
+// that dies without dropping its handles leaves its entries behind.
+//
+// One process formats the segment, the others attach to it:
+//
+//     #[derive(Clone, Copy, Debug)]
+//     #[repr(C)]
+//     struct Pid(u32);
+//     unsafe impl Pod for Pid {}
+//
+//     let registry = unsafe { ShmRegistry::<Pid>::format(ptr, len, 64) }?;
+//     let me = registry.alloc(Pid(42)).unwrap();
+//     registry.insert(&me);
+//
+//     let registry = unsafe { ShmRegistry::<Pid>::attach(ptr, len) }?;
+//     registry.for_each(|pid| println!("{:?}", pid));
+//
+// Payloads are copied in and out as plain bytes, written by any process
+// mapping the segment, so `T` has to be `Pod`.
+
+use alloc::vec::Vec;
+use core::hint;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const MAGIC: u32 = 0x5753_4d31;
+const NONE: u32 = u32::MAX;
+
+// Everything but `magic` and `lock` is only touched with the lock held, so
+// the relaxed accesses are ordered by the lock itself
+#[repr(C)]
+struct Header {
+    magic: AtomicU32,
+    lock: AtomicU32,
+    capacity: AtomicU32,
+    entry_size: AtomicU32,
+    len: AtomicU32,
+    free: AtomicU32,
+}
+
+#[repr(C)]
+struct Slot<T> {
+    // Index in the member table, `NONE` while not registered
+    index: AtomicU32,
+    // Next free entry while in the pool
+    next: AtomicU32,
+    x: MaybeUninit<T>,
+}
+
+// Types that can be read back from bytes another process wrote.
+//
+// Safety: every bit pattern must be a valid value (so no `bool`, `char`,
+// enums or references), and the type must not hold pointers, which mean
+// nothing in another address space. Processes built separately only agree
+// on a `#[repr(C)]` (or primitive) layout.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! pod {
+    ($($t:ty),*) => { $(unsafe impl Pod for $t {})* };
+}
+
+pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShmError {
+    // The segment can't hold the header, table and pool
+    TooSmall,
+    Misaligned,
+    // `attach` found no formatted registry
+    NotFormatted,
+    // The segment was formatted for a different payload type
+    LayoutMismatch,
+}
+
+pub struct ShmRegistry<'m, T: Pod> {
+    base: NonNull<u8>,
+    capacity: u32,
+    _marker: PhantomData<(&'m [u8], T)>,
+}
+
+pub struct ShmEntry<'r, T: Pod> {
+    registry: &'r ShmRegistry<'r, T>,
+    offset: u32,
+}
+
+struct Locked<'a>(&'a AtomicU32);
+
+impl<'a> Drop for Locked<'a> {
+    fn drop(&mut self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+fn layout<T>(capacity: u32) -> (usize, usize) {
+    let table = align_up(mem::size_of::<Header>(), mem::align_of::<AtomicU32>());
+    let entries = align_up(table + capacity as usize * mem::size_of::<AtomicU32>(), mem::align_of::<Slot<T>>());
+    (table, entries)
+}
+
+fn align_up(n: usize, align: usize) -> usize {
+    (n + align - 1) & !(align - 1)
+}
+
+impl<'m, T: Pod> ShmRegistry<'m, T> {
+    // Lay out an empty registry with room for `capacity` entries. Processes
+    // must not attach before this returns.
+    //
+    // Safety: `mem` must be valid for `len` bytes for `'m`, and no other
+    // process may be using the segment yet.
+    pub unsafe fn format(mem: *mut u8, len: usize, capacity: u32) -> Result<Self, ShmError> {
+        let registry = Self::check(mem, len, capacity)?;
+        let header = registry.header();
+        header.lock.store(0, Ordering::Relaxed);
+        header.capacity.store(capacity, Ordering::Relaxed);
+        header.entry_size.store(mem::size_of::<Slot<T>>() as u32, Ordering::Relaxed);
+        header.len.store(0, Ordering::Relaxed);
+        header.free.store(if capacity == 0 { NONE } else { registry.entry_offset(0) }, Ordering::Relaxed);
+        for i in 0..capacity {
+            let next = if i + 1 < capacity { registry.entry_offset(i + 1) } else { NONE };
+            let slot = registry.slot(registry.entry_offset(i));
+            slot.index.store(NONE, Ordering::Relaxed);
+            slot.next.store(next, Ordering::Relaxed);
+        }
+        header.magic.store(MAGIC, Ordering::Release);
+        Ok(registry)
+    }
+
+    // Safety: `mem` must be valid for `len` bytes for `'m` and hold a
+    // registry formatted for the same `T`.
+    pub unsafe fn attach(mem: *mut u8, len: usize) -> Result<Self, ShmError> {
+        if len < mem::size_of::<Header>() {
+            return Err(ShmError::TooSmall);
+        }
+        if !(mem as usize).is_multiple_of(mem::align_of::<Header>()) {
+            return Err(ShmError::Misaligned);
+        }
+        let header = &*(mem as *const Header);
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(ShmError::NotFormatted);
+        }
+        if header.entry_size.load(Ordering::Relaxed) != mem::size_of::<Slot<T>>() as u32 {
+            return Err(ShmError::LayoutMismatch);
+        }
+        Self::check(mem, len, header.capacity.load(Ordering::Relaxed))
+    }
+
+    unsafe fn check(mem: *mut u8, len: usize, capacity: u32) -> Result<Self, ShmError> {
+        let (_, entries) = layout::<T>(capacity);
+        let size = capacity as usize * mem::size_of::<Slot<T>>();
+        if entries + size > len || entries + size > NONE as usize {
+            return Err(ShmError::TooSmall);
+        }
+        let align = mem::align_of::<Header>().max(mem::align_of::<Slot<T>>());
+        if !(mem as usize).is_multiple_of(align) {
+            return Err(ShmError::Misaligned);
+        }
+        let base = NonNull::new(mem).ok_or(ShmError::Misaligned)?;
+        Ok(ShmRegistry { base, capacity, _marker: PhantomData })
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.base.as_ptr() as *const Header) }
+    }
+
+    fn lock(&self) -> Locked<'_> {
+        let lock = &self.header().lock;
+        while lock.compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            hint::spin_loop();
+        }
+        Locked(lock)
+    }
+
+    fn table(&self, i: u32) -> &AtomicU32 {
+        let (table, _) = layout::<T>(self.capacity);
+        unsafe { &*(self.base.as_ptr().add(table + i as usize * mem::size_of::<AtomicU32>()) as *const AtomicU32) }
+    }
+
+    fn entry_offset(&self, i: u32) -> u32 {
+        let (_, entries) = layout::<T>(self.capacity);
+        (entries + i as usize * mem::size_of::<Slot<T>>()) as u32
+    }
+
+    fn slot(&self, offset: u32) -> &Slot<T> {
+        unsafe { &*(self.base.as_ptr().add(offset as usize) as *const Slot<T>) }
+    }
+
+    fn value(&self, offset: u32) -> T {
+        unsafe { ptr::read(self.slot(offset).x.as_ptr()) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity as usize
+    }
+
+    pub fn len(&self) -> usize {
+        let _locked = self.lock();
+        self.header().len.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Take an entry from the pool, or `None` if every entry is in use. The
+    // entry isn't registered yet.
+    pub fn alloc(&self, x: T) -> Option<ShmEntry<'_, T>> {
+        let _locked = self.lock();
+        let header = self.header();
+        let offset = header.free.load(Ordering::Relaxed);
+        if offset == NONE {
+            return None;
+        }
+        let slot = self.slot(offset);
+        header.free.store(slot.next.load(Ordering::Relaxed), Ordering::Relaxed);
+        slot.index.store(NONE, Ordering::Relaxed);
+        unsafe { ptr::write(slot.x.as_ptr() as *mut T, x) };
+        Some(ShmEntry { registry: self, offset })
+    }
+
+    pub fn insert(&self, entry: &ShmEntry<'_, T>) {
+        assert!(ptr::eq(entry.registry.base.as_ptr(), self.base.as_ptr()), "entry belongs to another registry");
+        let _locked = self.lock();
+        let slot = self.slot(entry.offset);
+        if slot.index.load(Ordering::Relaxed) != NONE {
+            return;
+        }
+        let len = &self.header().len;
+        let i = len.load(Ordering::Relaxed);
+        self.table(i).store(entry.offset, Ordering::Relaxed);
+        slot.index.store(i, Ordering::Relaxed);
+        len.store(i + 1, Ordering::Relaxed);
+    }
+
+    pub fn remove(&self, entry: &ShmEntry<'_, T>) -> bool {
+        if !ptr::eq(entry.registry.base.as_ptr(), self.base.as_ptr()) {
+            return false;
+        }
+        let _locked = self.lock();
+        self.unlink(entry.offset)
+    }
+
+    // Swap-remove from the member table. Needs the lock.
+    fn unlink(&self, offset: u32) -> bool {
+        let slot = self.slot(offset);
+        let i = slot.index.load(Ordering::Relaxed);
+        if i == NONE {
+            return false;
+        }
+        let len = &self.header().len;
+        let last = len.load(Ordering::Relaxed) - 1;
+        if i != last {
+            let moved = self.table(last).load(Ordering::Relaxed);
+            self.table(i).store(moved, Ordering::Relaxed);
+            self.slot(moved).index.store(i, Ordering::Relaxed);
+        }
+        slot.index.store(NONE, Ordering::Relaxed);
+        len.store(last, Ordering::Relaxed);
+        true
+    }
+
+    // The registered payloads, copied out under the lock
+    pub fn members(&self) -> Vec<T> {
+        let _locked = self.lock();
+        let len = self.header().len.load(Ordering::Relaxed);
+        (0..len).map(|i| self.value(self.table(i).load(Ordering::Relaxed))).collect()
+    }
+
+    // Works on a copy, so `f` may drop or register entries of its own
+    pub fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.members().iter().for_each(f)
+    }
+}
+
+impl<'r, T: Pod> ShmEntry<'r, T> {
+    // The payload is only written by `alloc`, so reading it needs no lock
+    pub fn get(&self) -> T {
+        self.registry.value(self.offset)
+    }
+
+    // Where the entry sits in the segment; the same in every process
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn is_registered(&self) -> bool {
+        let _locked = self.registry.lock();
+        self.registry.slot(self.offset).index.load(Ordering::Relaxed) != NONE
+    }
+}
+
+impl<'r, T: Pod> Drop for ShmEntry<'r, T> {
+    fn drop(&mut self) {
+        let registry = self.registry;
+        let _locked = registry.lock();
+        registry.unlink(self.offset);
+        let header = registry.header();
+        registry.slot(self.offset).next.store(header.free.load(Ordering::Relaxed), Ordering::Relaxed);
+        header.free.store(self.offset, Ordering::Relaxed);
+    }
+}
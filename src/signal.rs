@@ -0,0 +1,169 @@
+// Signals and slots on top of `WeakSet`. Slots are closures or objects
+// implementing `Slot`; connecting one returns a `Connection` that owns its
+// pinned entry, so dropping the connection (or whatever owns it) is all it
+// takes to unsubscribe.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use core::pin::Pin;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{Entry, EntryId, PriorityStorage, WeakSet};
+
+pub trait Slot<A: ?Sized> {
+    fn call(&self, args: &A);
+}
+
+impl<A: ?Sized, F: Fn(&A)> Slot<A> for F {
+    fn call(&self, args: &A) {
+        self(args)
+    }
+}
+
+type SlotRef<A> = Rc<dyn Slot<A>>;
+// ordered so handlers can be given a precedence
+type SlotSet<A> = WeakSet<SlotRef<A>, PriorityStorage>;
+
+// The entry type of a slot, for objects that embed the entry themselves
+pub type SlotEntry<A> = Entry<SlotRef<A>>;
+
+pub struct Signal<A: ?Sized> {
+    slots: Pin<Box<SlotSet<A>>>,
+    // Open `Batch` guards, and the latest value posted while one is open
+    batches: Cell<usize>,
+    pending: RefCell<Option<Box<A>>>,
+}
+
+pub struct Connection<A: ?Sized> {
+    entry: Pin<Box<SlotEntry<A>>>,
+}
+
+impl<A: ?Sized> Signal<A> {
+    pub fn new() -> Self {
+        Signal {
+            slots: Box::pin(WeakSet::default()),
+            batches: Cell::new(0),
+            pending: RefCell::new(None),
+        }
+    }
+
+    pub fn connect<S: Slot<A> + 'static>(&self, slot: S) -> Connection<A> {
+        self.connect_with_priority(slot, 0)
+    }
+
+    // Slots with a higher priority are called first; slots with equal
+    // priorities are called in the order they were connected.
+    pub fn connect_with_priority<S: Slot<A> + 'static>(&self, slot: S, priority: i32) -> Connection<A> {
+        let entry = Box::pin(Entry::new(Rc::new(slot) as SlotRef<A>));
+        self.slots.as_ref().insert_with_priority(entry.as_ref(), priority);
+        Connection { entry }
+    }
+
+    // Connect a slot for the next emission only. The connection reports
+    // itself disconnected once the slot has run.
+    pub fn connect_once<S: Slot<A> + 'static>(&self, slot: S) -> Connection<A> {
+        let conn = self.connect(slot);
+        self.slots.as_ref().mark_once(conn.entry.as_ref());
+        conn
+    }
+
+    // Connect a slot entry owned elsewhere; it is disconnected when dropped
+    pub fn connect_entry(&self, entry: Pin<&SlotEntry<A>>, priority: i32) {
+        self.slots.as_ref().insert_with_priority(entry, priority);
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // Call every connected slot. A slot may connect or disconnect slots
+    // (including itself) while it runs: slots connected during an emission
+    // are first called by the next one, and slots disconnected before their
+    // turn are not called at all.
+    pub fn emit(&self, args: &A) {
+        self.slots.as_ref().for_each_strong(|slot| slot.call(args));
+    }
+
+    // Like `emit`, but every slot runs under `catch_unwind`, so one
+    // panicking slot doesn't keep the rest from being called. Returns the
+    // ids of the connections whose slot panicked.
+    #[cfg(feature = "std")]
+    pub fn emit_isolated(&self, args: &A) -> Vec<EntryId> {
+        let mut panicked = Vec::new();
+        self.slots.as_ref().dispatch_strong(|id, slot| {
+            if panic::catch_unwind(AssertUnwindSafe(|| slot.call(args))).is_err() {
+                panicked.push(id);
+            }
+        });
+        panicked
+    }
+}
+
+impl<A> Signal<A> {
+    // Emit `args`, unless a batch is open: then it replaces whatever was
+    // posted earlier in the batch and is emitted when the batch ends, so
+    // slots only see the final value.
+    pub fn post(&self, args: A) {
+        if self.batches.get() == 0 {
+            self.emit(&args);
+        } else {
+            *self.pending.borrow_mut() = Some(Box::new(args));
+        }
+    }
+
+    // Coalesce `post`s until the returned guard (and any other open batch)
+    // is dropped. Plain `emit`s are not affected.
+    pub fn batch(&self) -> Batch<'_, A> {
+        self.batches.set(self.batches.get() + 1);
+        Batch { signal: self }
+    }
+}
+
+impl<A: ?Sized> Signal<A> {
+    // Emit the value posted in the current batch now, if there is one
+    pub fn flush(&self) {
+        let pending = self.pending.borrow_mut().take();
+        if let Some(args) = pending {
+            trace!(slots = self.len(), "flush batched post");
+            self.emit(&args);
+        }
+    }
+}
+
+pub struct Batch<'a, A> {
+    signal: &'a Signal<A>,
+}
+
+impl<'a, A> Drop for Batch<'a, A> {
+    fn drop(&mut self) {
+        let batches = self.signal.batches.get() - 1;
+        self.signal.batches.set(batches);
+        if batches == 0 {
+            self.signal.flush();
+        }
+    }
+}
+
+impl<A: ?Sized> Default for Signal<A> {
+    fn default() -> Self {
+        Signal::new()
+    }
+}
+
+impl<A: ?Sized> Connection<A> {
+    pub fn id(&self) -> EntryId {
+        self.entry.id()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.entry.is_registered()
+    }
+
+    pub fn disconnect(self) {}
+}
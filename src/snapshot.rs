@@ -0,0 +1,48 @@
+// Serde support, for dumping live registries into debug reports and state
+// snapshots. A set serializes as the sequence of its members' payloads, in
+// iteration order; the metadata the set keeps isn't included. Such a
+// sequence deserializes into a `StrongSet` owning one new entry per payload,
+// which `register_all` then puts into a set to get the registry back.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use crate::{Storage, StrongSet, WeakSet};
+
+impl<T: Serialize, S: Storage> Serialize for Pin<&WeakSet<T, S>> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for entry in self.iter() {
+            seq.serialize_element(&entry.x)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StrongSet<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(StrongSetVisitor(PhantomData))
+    }
+}
+
+struct StrongSetVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for StrongSetVisitor<T> {
+    type Value = StrongSet<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of members")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<StrongSet<T>, A::Error> {
+        let mut members = StrongSet::new();
+        while let Some(x) = seq.next_element()? {
+            members.insert(x);
+        }
+        Ok(members)
+    }
+}
@@ -0,0 +1,1072 @@
+// Backing storage for the members of a `WeakSet`. The set only ever stores
+// type-erased `*const Entry<T>` pointers, so storages don't need to know about
+// `T`. Each member also carries a metadata value chosen by the storage's user
+// (`()` unless asked for), kept next to the pointer rather than in the entry.
+//
+// Every pointer a storage is handed points to a live entry, so storages may
+// use the position cell each entry starts with (see `slot`).
+
+use alloc::collections::{btree_map, BTreeMap};
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::cmp::Reverse;
+use core::hash::{BuildHasherDefault, Hasher};
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+
+use hashbrown::HashMap;
+
+use crate::{Entry, WeakSet};
+
+// Where a `WeakSet` keeps its member pointers.
+//
+// Safety: the set relies on a storage to faithfully report what was
+// inserted. `iter` must yield exactly the pointers that were inserted and
+// not removed since, each once, and `get`/`len` must agree with it.
+//
+// Storages may read and write the entries they are given (the dense ones
+// keep their index in `Entry::slot`), so the methods taking an entry are
+// unsafe to call. Safety: `entry` must point at a live, pinned `Entry` of
+// the set's payload type, and an inserted entry must stay so until it is
+// removed again.
+pub unsafe trait Storage {
+    type Meta;
+    type Iter<'a>: Iterator<Item = (*const (), &'a Self::Meta)>
+    where Self: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: Self::Meta);
+    // Called by the set just before it inserts `entry`, while the storage is
+    // only shared, for work that runs user code (hashing a key, say). The
+    // set never runs user code while it holds the storage exclusively.
+    unsafe fn prepare_insert(&self, _entry: *const ()) {}
+    // Returns None if `entry` wasn't stored
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<Self::Meta>;
+    unsafe fn get(&self, entry: *const ()) -> Option<&Self::Meta>;
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut Self::Meta>;
+    fn len(&self) -> usize;
+    fn iter(&self) -> Self::Iter<'_>;
+
+    unsafe fn contains(&self, entry: *const ()) -> bool {
+        self.get(entry).is_some()
+    }
+
+    // Like `contains`, but `entry` may dangle, so it can't be read
+    fn contains_addr(&self, entry: *const ()) -> bool {
+        self.iter().any(|(member, _)| member == entry)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // How many members fit without allocating
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    // Make room for `additional` more members, if the storage can
+    fn reserve(&mut self, _additional: usize) {}
+
+    // Give memory back, keeping room for at least `min` members
+    fn shrink_to(&mut self, _min: usize) {}
+
+    // Called before each pass over the members (iteration, broadcast)
+    // unless one is already in progress
+    fn before_iter(&mut self) {}
+
+    // Bytes of heap memory owned by the storage, possibly an estimate
+    fn heap_size(&self) -> usize {
+        0
+    }
+
+    // How often the storage has moved its members into a new table
+    fn rehashes(&self) -> usize {
+        0
+    }
+}
+
+// What a hash table with room for `capacity` entries of type `E` takes: the
+// buckets, at most 7/8 full, plus a control byte each
+pub(crate) fn table_size<E>(capacity: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    let buckets = (capacity * 8 / 7).next_power_of_two();
+    buckets * (core::mem::size_of::<E>() + 1)
+}
+
+// The position cell at the start of every entry. An entry is in at most one
+// storage at a time, and only storages that use the cell write to it, so its
+// value may be stale and has to be checked.
+pub(crate) unsafe fn slot<'a>(entry: *const ()) -> &'a Cell<usize> {
+    &*(entry as *const Cell<usize>)
+}
+
+// The bookkeeping of storages laid out like `DenseStorage`, whatever vector
+// they keep their members in: each entry's slot holds its index, and removal
+// is a swap-remove that updates the slot of the member moved into the gap.
+pub(crate) trait DenseMembers<M> {
+    fn members(&self) -> &[(*const (), M)];
+    fn members_mut(&mut self) -> &mut [(*const (), M)];
+    fn push(&mut self, member: (*const (), M));
+    fn swap_remove(&mut self, i: usize) -> (*const (), M);
+
+    unsafe fn index(&self, entry: *const ()) -> Option<usize> {
+        let i = unsafe { slot(entry) }.get();
+        match self.members().get(i) {
+            Some(&(member, _)) if member == entry => Some(i),
+            _ => None,
+        }
+    }
+
+    unsafe fn dense_insert(&mut self, entry: *const (), meta: M) {
+        if let Some(i) = self.index(entry) {
+            self.members_mut()[i].1 = meta;
+            return;
+        }
+        unsafe { slot(entry) }.set(self.members().len());
+        self.push((entry, meta));
+    }
+
+    unsafe fn dense_remove(&mut self, entry: *const ()) -> Option<M> {
+        let i = self.index(entry)?;
+        let (_, meta) = self.swap_remove(i);
+        if let Some(&(moved, _)) = self.members().get(i) {
+            unsafe { slot(moved) }.set(i);
+        }
+        Some(meta)
+    }
+
+    unsafe fn dense_get(&self, entry: *const ()) -> Option<&M> {
+        self.index(entry).map(|i| &self.members()[i].1)
+    }
+
+    unsafe fn dense_get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.index(entry).map(move |i| &mut self.members_mut()[i].1)
+    }
+}
+
+// The default storage: a dense array of members, each remembering its index
+// in the entry itself. Removal (and so dropping a member) is a swap-remove
+// plus one index update, with no hashing, and iteration is a linear scan over
+// contiguous memory; with 50k members that is about twice as fast as walking
+// a hash table (see `benches/backends.rs`).
+pub struct DenseStorage<M = ()> {
+    members: Vec<(*const (), M)>,
+}
+
+impl<M> Default for DenseStorage<M> {
+    fn default() -> Self {
+        DenseStorage::new()
+    }
+}
+
+impl<M> DenseStorage<M> {
+    pub const fn new() -> Self {
+        DenseStorage { members: Vec::new() }
+    }
+}
+
+impl<M> DenseMembers<M> for DenseStorage<M> {
+    fn members(&self) -> &[(*const (), M)] {
+        &self.members
+    }
+
+    fn members_mut(&mut self) -> &mut [(*const (), M)] {
+        &mut self.members
+    }
+
+    fn push(&mut self, member: (*const (), M)) {
+        self.members.push(member);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> (*const (), M) {
+        self.members.swap_remove(i)
+    }
+}
+
+// A `DenseStorage` that puts its members back in address order before a pass
+// over them, so visiting the payloads walks memory mostly forwards. That pays
+// off when entries come from an arena or pool and iteration is much more
+// frequent than membership changes: any removal but the last member's, and
+// any insert below the highest address, means sorting again next time.
+pub struct SortedStorage<M = ()> {
+    dense: DenseStorage<M>,
+    sorted: bool,
+}
+
+impl<M> Default for SortedStorage<M> {
+    fn default() -> Self {
+        SortedStorage { dense: DenseStorage::new(), sorted: true }
+    }
+}
+
+unsafe impl<M> Storage for SortedStorage<M> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        if let Some(&(last, _)) = self.dense.members.last() {
+            self.sorted &= last < entry;
+        }
+        self.dense.insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        let meta = self.dense.remove(entry)?;
+        // unless it was the last one, another member took its place
+        self.sorted &= self.dense.members.last().is_none_or(|&(last, _)| last < entry);
+        Some(meta)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense.get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense.get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.dense.iter()
+    }
+
+    fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.dense.shrink_to(min);
+    }
+
+    fn heap_size(&self) -> usize {
+        self.dense.heap_size()
+    }
+
+    fn before_iter(&mut self) {
+        if !self.sorted {
+            self.dense.members.sort_unstable_by_key(|&(entry, _)| entry);
+            for (i, &(entry, _)) in self.dense.members.iter().enumerate() {
+                unsafe { slot(entry) }.set(i);
+            }
+            self.sorted = true;
+        }
+    }
+}
+
+pub struct DenseIter<'a, M> {
+    base: core::slice::Iter<'a, (*const (), M)>,
+}
+
+impl<'a, M> DenseIter<'a, M> {
+    pub(crate) fn new(members: &'a [(*const (), M)]) -> Self {
+        DenseIter { base: members.iter() }
+    }
+}
+
+impl<'a, M> Iterator for DenseIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(entry, meta)| (*entry, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, M> DoubleEndedIterator for DenseIter<'a, M> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.base.next_back().map(|(entry, meta)| (*entry, meta))
+    }
+}
+
+impl<'a, M> ExactSizeIterator for DenseIter<'a, M> {}
+
+unsafe impl<M> Storage for DenseStorage<M> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.dense_insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.dense_remove(entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense_get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense_get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        DenseIter::new(&self.members)
+    }
+
+    fn capacity(&self) -> usize {
+        self.members.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.members.reserve(additional);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.members.shrink_to(min);
+    }
+
+    fn heap_size(&self) -> usize {
+        self.members.capacity() * core::mem::size_of::<(*const (), M)>()
+    }
+}
+
+// Like `DenseStorage`, but the first `N` members are kept inline in the set
+// itself, so sets that rarely have more than a couple of members (a widget's
+// listeners) never allocate. Past `N` members everything moves to the heap,
+// and moves back once `shrink_to` finds that it fits again.
+pub struct InlineStorage<M = (), const N: usize = 2> {
+    inline: [MaybeUninit<(*const (), M)>; N],
+    // initialized prefix of `inline`, while not spilled
+    len: usize,
+    spilled: bool,
+    heap: Vec<(*const (), M)>,
+}
+
+impl<M, const N: usize> Default for InlineStorage<M, N> {
+    fn default() -> Self {
+        InlineStorage {
+            inline: [(); N].map(|_| MaybeUninit::uninit()),
+            len: 0,
+            spilled: false,
+            heap: Vec::new(),
+        }
+    }
+}
+
+impl<M, const N: usize> DenseMembers<M> for InlineStorage<M, N> {
+    fn members(&self) -> &[(*const (), M)] {
+        if self.spilled {
+            &self.heap
+        } else {
+            unsafe { core::slice::from_raw_parts(self.inline.as_ptr() as *const _, self.len) }
+        }
+    }
+
+    fn members_mut(&mut self) -> &mut [(*const (), M)] {
+        if self.spilled {
+            &mut self.heap
+        } else {
+            unsafe { core::slice::from_raw_parts_mut(self.inline.as_mut_ptr() as *mut _, self.len) }
+        }
+    }
+
+    fn push(&mut self, member: (*const (), M)) {
+        if self.spilled {
+            self.heap.push(member);
+        } else if self.len < N {
+            self.inline[self.len].write(member);
+            self.len += 1;
+        } else {
+            let mut heap = Vec::with_capacity(N * 2);
+            heap.extend(self.inline.iter().map(|member| unsafe { member.assume_init_read() }));
+            heap.push(member);
+            self.heap = heap;
+            self.len = 0;
+            self.spilled = true;
+        }
+    }
+
+    fn swap_remove(&mut self, i: usize) -> (*const (), M) {
+        if self.spilled {
+            return self.heap.swap_remove(i);
+        }
+        let last = self.len - 1;
+        self.members_mut().swap(i, last);
+        self.len = last;
+        unsafe { self.inline[last].assume_init_read() }
+    }
+}
+
+impl<M, const N: usize> Drop for InlineStorage<M, N> {
+    fn drop(&mut self) {
+        if !self.spilled {
+            unsafe { core::ptr::drop_in_place(self.members_mut()) };
+        }
+    }
+}
+
+unsafe impl<M, const N: usize> Storage for InlineStorage<M, N> {
+    type Meta = M;
+    type Iter<'a> = DenseIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.dense_insert(entry, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.dense_remove(entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense_get(entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense_get_mut(entry)
+    }
+
+    fn len(&self) -> usize {
+        self.members().len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        DenseIter { base: self.members().iter() }
+    }
+
+    fn capacity(&self) -> usize {
+        if self.spilled { self.heap.capacity() } else { N }
+    }
+
+    fn heap_size(&self) -> usize {
+        self.heap.capacity() * core::mem::size_of::<(*const (), M)>()
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        if !self.spilled {
+            return;
+        }
+        if self.heap.len() <= N && min <= N {
+            // Indices stay the same, so the entries' slots stay valid
+            self.len = 0;
+            for (i, member) in self.heap.drain(..).enumerate() {
+                self.inline[i].write(member);
+                self.len = i + 1;
+            }
+            self.heap = Vec::new();
+            self.spilled = false;
+        } else {
+            self.heap.shrink_to(min);
+        }
+    }
+}
+
+// Hashes member pointers with a single multiply. Keys are addresses of live
+// entries rather than attacker-chosen data, so SipHash's DoS resistance buys
+// nothing here. Measured against SipHash with 200k boxed entries on x86_64
+// (release build): insert 48 -> 27 ns, `remove` 63 -> 18 ns, and dropping a
+// registered entry 81 -> 30 ns.
+#[derive(Default, Clone, Copy)]
+pub struct PtrHasher(u64);
+
+impl Hasher for PtrHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, x: u64) {
+        self.0 = (self.0 ^ x).wrapping_mul(0xf135_7aea_2e62_a9c5);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, x: usize) {
+        self.write_u64(x as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        // The low bits of the product are as poorly mixed as the low bits of
+        // the aligned address, so bring the high ones down
+        self.0.rotate_left(26)
+    }
+}
+
+pub type PtrBuildHasher = BuildHasherDefault<PtrHasher>;
+
+pub(crate) type PtrMap<V> = HashMap<*const (), V, PtrBuildHasher>;
+
+pub struct HashStorage<M = ()> {
+    objects: PtrMap<M>,
+    rehashes: usize,
+}
+
+impl<M> Default for HashStorage<M> {
+    fn default() -> Self {
+        HashStorage { objects: PtrMap::default(), rehashes: 0 }
+    }
+}
+
+impl<M> HashStorage<M> {
+    // The map resizes on its own, so see whether that happened
+    fn count_rehash(&mut self, capacity: usize) {
+        if capacity != 0 && self.objects.capacity() != capacity {
+            self.rehashes += 1;
+        }
+    }
+}
+
+pub struct HashIter<'a, M> {
+    base: hashbrown::hash_map::Iter<'a, *const (), M>,
+}
+
+impl<'a, M> Iterator for HashIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(&entry, meta)| (entry, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, M> ExactSizeIterator for HashIter<'a, M> {}
+
+unsafe impl<M> Storage for HashStorage<M> {
+    type Meta = M;
+    type Iter<'a> = HashIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        let capacity = self.objects.capacity();
+        self.objects.insert(entry, meta);
+        self.count_rehash(capacity);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        self.objects.remove(&entry)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.objects.get(&entry)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.objects.get_mut(&entry)
+    }
+
+    // Hashing only looks at the address
+    fn contains_addr(&self, entry: *const ()) -> bool {
+        self.objects.contains_key(&entry)
+    }
+
+    fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        HashIter { base: self.objects.iter() }
+    }
+
+    fn capacity(&self) -> usize {
+        self.objects.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let capacity = self.objects.capacity();
+        self.objects.reserve(additional);
+        self.count_rehash(capacity);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        let capacity = self.objects.capacity();
+        self.objects.shrink_to(min);
+        if !self.objects.is_empty() {
+            self.count_rehash(capacity);
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        table_size::<(*const (), M)>(self.objects.capacity())
+    }
+
+    fn rehashes(&self) -> usize {
+        self.rehashes
+    }
+}
+
+// An open-addressed table for sets with constant insert/remove churn. Removal
+// leaves a tombstone that a later insert probing past it can reuse, and
+// tombstones at the end of a probe run are cleared on the spot, so steady
+// turnover neither rehashes nor lets tombstones pile up. Each member's hash
+// is cached in its entry's position cell at insert, so removal (on drop) and
+// rehashing don't hash again. (`HashStorage` can't do the same, its map has
+// no way to look up by a precomputed hash.)
+//
+// Created with `incremental()`, growing the table doesn't move every member
+// at once: the old table is kept alongside and a few of its buckets are
+// moved over by each insert and removal. The new table still has to be
+// allocated and cleared in one go, but that's several times cheaper than
+// moving every member (a million inserts: 44 ms worst case down to 15 ms).
+pub struct OpenStorage<M = ()> {
+    buckets: Vec<Bucket<M>>,
+    len: usize,
+    tombstones: usize,
+    incremental: bool,
+    // The table being migrated away from, with the number of members still
+    // in it. Buckets before `cursor` have been moved already.
+    old: Vec<Bucket<M>>,
+    old_len: usize,
+    cursor: usize,
+    rehashes: usize,
+}
+
+enum Bucket<M> {
+    Empty,
+    Tombstone,
+    Full(*const (), M),
+}
+
+// Old-table buckets moved per operation. Growing doubles the table, so the
+// old one (half the new size, at most 7/8 full) is gone well before the new
+// one fills up.
+const MIGRATE_STEP: usize = 8;
+
+impl<M> Default for OpenStorage<M> {
+    fn default() -> Self {
+        OpenStorage {
+            buckets: Vec::new(),
+            len: 0,
+            tombstones: 0,
+            incremental: false,
+            old: Vec::new(),
+            old_len: 0,
+            cursor: 0,
+            rehashes: 0,
+        }
+    }
+}
+
+// Where a member was found
+enum Place {
+    New(usize),
+    Old(usize),
+}
+
+
+impl<M> OpenStorage<M> {
+    pub fn incremental() -> Self {
+        OpenStorage { incremental: true, ..OpenStorage::default() }
+    }
+
+    fn hash(entry: *const ()) -> usize {
+        let mut hasher = PtrHasher::default();
+        hasher.write_usize(entry as usize);
+        hasher.finish() as usize
+    }
+
+    // The hash stored at insert. Stale if `entry` isn't a member, but then
+    // there's nothing to find anyway.
+    fn cached_hash(entry: *const ()) -> usize {
+        unsafe { slot(entry) }.get()
+    }
+
+    fn next(&self, i: usize) -> usize {
+        (i + 1) & (self.buckets.len() - 1)
+    }
+
+    fn find(&self, entry: *const (), hash: usize) -> Option<Place> {
+        if let Some(i) = self.probe(entry, hash) {
+            return Some(Place::New(i));
+        }
+        if self.old_len > 0 {
+            return self.probe_old(entry, hash).map(Place::Old);
+        }
+        None
+    }
+
+    // The table always has an empty bucket, so this stops
+    fn probe(&self, entry: *const (), hash: usize) -> Option<usize> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let mut i = hash & (self.buckets.len() - 1);
+        loop {
+            match self.buckets[i] {
+                Bucket::Empty => return None,
+                Bucket::Full(member, _) if member == entry => return Some(i),
+                _ => i = self.next(i),
+            }
+        }
+    }
+
+    // The old table fills up with tombstones as it's migrated, so it may
+    // have no empty bucket left; probe at most once around it. Everything
+    // before `cursor` has moved already and is skipped.
+    fn probe_old(&self, entry: *const (), hash: usize) -> Option<usize> {
+        let mask = self.old.len() - 1;
+        let mut i = (hash & mask).max(self.cursor);
+        for _ in self.cursor..self.old.len() {
+            match self.old[i] {
+                Bucket::Empty => return None,
+                Bucket::Full(member, _) if member == entry => return Some(i),
+                _ => i = ((i + 1) & mask).max(self.cursor),
+            }
+        }
+        None
+    }
+
+    fn bucket_mut(&mut self, place: Place) -> &mut Bucket<M> {
+        match place {
+            Place::New(i) => &mut self.buckets[i],
+            Place::Old(i) => &mut self.old[i],
+        }
+    }
+
+    // Put `entry` in the first reusable bucket of its probe sequence
+    fn place(&mut self, entry: *const (), hash: usize, meta: M) {
+        let mut i = hash & (self.buckets.len() - 1);
+        loop {
+            match self.buckets[i] {
+                Bucket::Empty => break,
+                Bucket::Tombstone => {
+                    self.tombstones -= 1;
+                    break;
+                }
+                Bucket::Full(..) => i = self.next(i),
+            }
+        }
+        self.buckets[i] = Bucket::Full(entry, meta);
+        self.len += 1;
+    }
+
+    // Move up to `n` buckets' worth of the old table over
+    fn migrate(&mut self, mut n: usize) {
+        while n > 0 && self.old_len > 0 {
+            // Moved buckets become tombstones so that the probe runs of
+            // members still in the old table stay intact
+            if let Bucket::Full(entry, meta) = core::mem::replace(&mut self.old[self.cursor], Bucket::Tombstone) {
+                self.old_len -= 1;
+                self.place(entry, Self::cached_hash(entry), meta);
+            }
+            self.cursor += 1;
+            n -= 1;
+        }
+        if self.old_len == 0 && !self.old.is_empty() {
+            self.old = Vec::new();
+            self.cursor = 0;
+        }
+    }
+
+    fn rehash(&mut self, capacity: usize) {
+        self.migrate(usize::MAX);
+        if self.len > 0 {
+            self.rehashes += 1;
+        }
+        let old = core::mem::replace(&mut self.buckets, (0..capacity).map(|_| Bucket::Empty).collect());
+        self.len = 0;
+        self.tombstones = 0;
+        for bucket in old {
+            if let Bucket::Full(entry, meta) = bucket {
+                self.place(entry, Self::cached_hash(entry), meta);
+            }
+        }
+    }
+
+    // The smallest table that holds `n` members
+    fn buckets_for(n: usize) -> usize {
+        if n == 0 { 0 } else { ((n + 1) * 8 / 7 + 1).next_power_of_two().max(8) }
+    }
+
+    // Start migrating to a table of `capacity` buckets
+    fn grow_incrementally(&mut self, capacity: usize) {
+        self.migrate(usize::MAX);
+        self.rehashes += 1;
+        self.old = core::mem::replace(&mut self.buckets, (0..capacity).map(|_| Bucket::Empty).collect());
+        self.old_len = self.len;
+        self.len = 0;
+        self.tombstones = 0;
+        self.cursor = 0;
+    }
+}
+
+pub struct OpenIter<'a, M> {
+    base: core::iter::Chain<core::slice::Iter<'a, Bucket<M>>, core::slice::Iter<'a, Bucket<M>>>,
+}
+
+impl<'a, M> Iterator for OpenIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.find_map(|bucket| match bucket {
+            Bucket::Full(entry, meta) => Some((*entry, meta)),
+            _ => None,
+        })
+    }
+}
+
+unsafe impl<M> Storage for OpenStorage<M> {
+    type Meta = M;
+    type Iter<'a> = OpenIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        let hash = Self::hash(entry);
+        if let Some(place) = self.find(entry, hash) {
+            *self.bucket_mut(place) = Bucket::Full(entry, meta);
+            return;
+        }
+        self.migrate(MIGRATE_STEP);
+        // Keep at most 7/8 of the buckets in use, counting members that
+        // are still to be migrated. Mostly tombstones means a same-size
+        // rehash is enough to make room.
+        let capacity = self.buckets.len();
+        let len = self.len + self.old_len;
+        if (len + self.tombstones + 1) * 8 > capacity * 7 {
+            if (len + 1) * 2 <= capacity {
+                self.rehash(capacity);
+            } else if self.incremental && capacity > 0 {
+                self.grow_incrementally(capacity * 2);
+            } else {
+                self.rehash((capacity * 2).max(8));
+            }
+        }
+        unsafe { slot(entry) }.set(hash);
+        self.place(entry, hash, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        let meta = match self.find(entry, Self::cached_hash(entry))? {
+            Place::Old(i) => {
+                self.old_len -= 1;
+                match core::mem::replace(&mut self.old[i], Bucket::Tombstone) {
+                    Bucket::Full(_, meta) => meta,
+                    _ => unreachable!(),
+                }
+            }
+            Place::New(i) => {
+                self.len -= 1;
+                let next_empty = matches!(self.buckets[self.next(i)], Bucket::Empty);
+                let bucket = if next_empty { Bucket::Empty } else { Bucket::Tombstone };
+                let meta = match core::mem::replace(&mut self.buckets[i], bucket) {
+                    Bucket::Full(_, meta) => meta,
+                    _ => unreachable!(),
+                };
+                if next_empty {
+                    // No probe run continues past here anymore, so the
+                    // tombstones leading up to this bucket are dead too
+                    let mask = self.buckets.len() - 1;
+                    let mut j = i.wrapping_sub(1) & mask;
+                    while let Bucket::Tombstone = self.buckets[j] {
+                        self.buckets[j] = Bucket::Empty;
+                        self.tombstones -= 1;
+                        j = j.wrapping_sub(1) & mask;
+                    }
+                } else {
+                    self.tombstones += 1;
+                }
+                meta
+            }
+        };
+        self.migrate(MIGRATE_STEP);
+        Some(meta)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        let bucket = match self.find(entry, Self::cached_hash(entry))? {
+            Place::New(i) => &self.buckets[i],
+            Place::Old(i) => &self.old[i],
+        };
+        match bucket {
+            Bucket::Full(_, meta) => Some(meta),
+            _ => None,
+        }
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        let place = self.find(entry, Self::cached_hash(entry))?;
+        match self.bucket_mut(place) {
+            Bucket::Full(_, meta) => Some(meta),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len + self.old_len
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        OpenIter { base: self.buckets.iter().chain(self.old.iter()) }
+    }
+
+    fn capacity(&self) -> usize {
+        self.buckets.len() / 8 * 7
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let capacity = Self::buckets_for(self.len + self.old_len + additional);
+        if capacity > self.buckets.len() {
+            self.rehash(capacity);
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        (self.buckets.capacity() + self.old.capacity()) * core::mem::size_of::<Bucket<M>>()
+    }
+
+    fn rehashes(&self) -> usize {
+        self.rehashes
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.migrate(usize::MAX);
+        let capacity = Self::buckets_for(min.max(self.len));
+        if capacity < self.buckets.len() {
+            self.rehash(capacity);
+        }
+    }
+}
+
+// Keeps members ordered by priority, highest first and in insertion order
+// among equal priorities, so iteration and dispatch visit them in that order.
+// Members inserted through the plain `insert` get priority 0.
+pub struct PriorityStorage<M = ()> {
+    order: BTreeMap<(Reverse<i32>, u64), (*const (), M)>,
+    keys: PtrMap<(Reverse<i32>, u64)>,
+    next_seq: u64,
+}
+
+impl<M> Default for PriorityStorage<M> {
+    fn default() -> Self {
+        PriorityStorage { order: BTreeMap::new(), keys: PtrMap::default(), next_seq: 0 }
+    }
+}
+
+impl<M> PriorityStorage<M> {
+    // Safety: as for `Storage::insert`.
+    pub unsafe fn insert_with_priority(&mut self, entry: *const (), priority: i32, meta: M) {
+        let key = (Reverse(priority), self.next_seq);
+        self.next_seq += 1;
+        if let Some(old) = self.keys.insert(entry, key) {
+            self.order.remove(&old);
+        }
+        self.order.insert(key, (entry, meta));
+    }
+
+    // Safety: as for `Storage::get`.
+    pub unsafe fn priority(&self, entry: *const ()) -> Option<i32> {
+        self.keys.get(&entry).map(|&(Reverse(priority), _)| priority)
+    }
+}
+
+pub struct PriorityIter<'a, M> {
+    base: btree_map::Values<'a, (Reverse<i32>, u64), (*const (), M)>,
+}
+
+impl<'a, M> Iterator for PriorityIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(entry, meta)| (*entry, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, M> DoubleEndedIterator for PriorityIter<'a, M> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.base.next_back().map(|(entry, meta)| (*entry, meta))
+    }
+}
+
+impl<'a, M> ExactSizeIterator for PriorityIter<'a, M> {}
+
+unsafe impl<M> Storage for PriorityStorage<M> {
+    type Meta = M;
+    type Iter<'a> = PriorityIter<'a, M>
+    where M: 'a;
+
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        self.insert_with_priority(entry, 0, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        let key = self.keys.remove(&entry)?;
+        self.order.remove(&key).map(|(_, meta)| meta)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        let key = self.keys.get(&entry)?;
+        self.order.get(key).map(|(_, meta)| meta)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        let key = self.keys.get(&entry)?;
+        self.order.get_mut(key).map(|(_, meta)| meta)
+    }
+
+    fn contains_addr(&self, entry: *const ()) -> bool {
+        self.keys.contains_key(&entry)
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        PriorityIter { base: self.order.values() }
+    }
+
+    fn capacity(&self) -> usize {
+        self.keys.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.keys.reserve(additional);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.keys.shrink_to(min);
+    }
+
+    // Counts the tree's entries but not its node overhead
+    fn heap_size(&self) -> usize {
+        table_size::<(*const (), (Reverse<i32>, u64))>(self.keys.capacity())
+            + self.order.len() * core::mem::size_of::<((Reverse<i32>, u64), (*const (), M))>()
+    }
+}
+
+impl<T, M> WeakSet<T, PriorityStorage<M>> {
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert_with_priority(self: Pin<&Self>, entry: Pin<&Entry<T>>, priority: i32)
+    where M: Default
+    {
+        self.link(entry, |objects, ptr| unsafe { objects.insert_with_priority(ptr, priority, M::default()) });
+    }
+
+    pub fn priority(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> Option<i32> {
+        unsafe { self.objects.borrow().priority(&*entry as *const Entry<T> as *const ()) }
+    }
+}
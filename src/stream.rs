@@ -0,0 +1,129 @@
+// Membership events as an async `Stream`, for a supervisor task observing
+// registrations and deregistrations. The stream is lossy: the set never
+// waits for the consumer, and events are buffered until polled, up to a
+// capacity. Events arriving while the buffer is full are dropped, and once
+// the consumer has caught up with the ones before them it gets a `Lagged`
+// with how many it missed, after which events flow again. The stream ends
+// once the set is dropped.
+//
+// The consumer's task is woken after the set has finished delivering the
+// event, so it may poll, and use the set, from inside `wake`.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use crate::{MembershipEvent, Storage, WeakSet};
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+// Events dropped because the stream's buffer was full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lagged(pub u64);
+
+struct Shared {
+    events: VecDeque<MembershipEvent>,
+    capacity: usize,
+    // dropped since the buffer filled up; nothing is buffered again until
+    // the consumer has been told
+    missed: u64,
+    waker: Option<Waker>,
+    // set when the set is dropped
+    closed: bool,
+}
+
+pub struct ChangesStream {
+    shared: Arc<Mutex<Shared>>,
+}
+
+// Held by the set; marks the stream closed when the set drops it
+struct Sink {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sink {
+    fn send(&self, event: MembershipEvent, wakers: &mut Vec<Waker>) -> bool {
+        // the stream was dropped
+        if Arc::strong_count(&self.shared) == 1 {
+            return false;
+        }
+        let mut shared = self.shared.lock().unwrap();
+        if shared.missed > 0 || shared.events.len() == shared.capacity {
+            shared.missed += 1;
+        } else {
+            shared.events.push_back(event);
+        }
+        wakers.extend(shared.waker.take());
+        true
+    }
+}
+
+impl Drop for Sink {
+    fn drop(&mut self) {
+        // Nobody to wake, and the set may be delivering an event: it drops
+        // the sinks of streams that are gone
+        if Arc::strong_count(&self.shared) == 1 {
+            return;
+        }
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.closed = true;
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T, S: Storage> WeakSet<T, S> {
+    // A stream of the set's membership changes, buffering at most 1024
+    // events; the rest are dropped and reported as `Lagged`
+    pub fn changes_stream(&self) -> ChangesStream {
+        self.changes_stream_with_capacity(DEFAULT_CAPACITY)
+    }
+
+    // As `changes_stream`, buffering at most `capacity` events
+    pub fn changes_stream_with_capacity(&self, capacity: usize) -> ChangesStream {
+        let shared = Arc::new(Mutex::new(Shared {
+            events: VecDeque::new(),
+            capacity,
+            missed: 0,
+            waker: None,
+            closed: false,
+        }));
+        let sink = Sink { shared: shared.clone() };
+        self.subscribe(Box::new(move |event, wakers| sink.send(event, wakers)));
+        ChangesStream { shared }
+    }
+}
+
+impl Stream for ChangesStream {
+    type Item = Result<MembershipEvent, Lagged>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(event) = shared.events.pop_front() {
+            return Poll::Ready(Some(Ok(event)));
+        }
+        if shared.missed > 0 {
+            let missed = shared.missed;
+            shared.missed = 0;
+            return Poll::Ready(Some(Err(Lagged(missed))));
+        }
+        if shared.closed {
+            return Poll::Ready(None);
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let shared = self.shared.lock().unwrap();
+        let ready = shared.events.len() + usize::from(shared.missed > 0);
+        (ready, if shared.closed { Some(ready) } else { None })
+    }
+}
@@ -0,0 +1,136 @@
+// An owning companion to `WeakSet`. A registry often owns some of its members
+// and merely observes others; `StrongSet` holds the owned ones pinned on the
+// heap, and they can be registered into any number of `WeakSet`s (each entry
+// still belongs to at most one at a time) while the others stay weakly held.
+//
+// Slots are reused, so as in `EntryPool` each carries a generation that is
+// bumped on both insert and release, and a `StrongKey` for a slot that has
+// been reused since doesn't find the new member.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+use core::pin::Pin;
+
+use crate::{Entry, Storage, WeakSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StrongKey {
+    index: usize,
+    generation: u32,
+}
+
+struct Slot<T> {
+    generation: u32,
+    entry: Option<Pin<Box<Entry<T>>>>,
+}
+
+pub struct StrongSet<T> {
+    slots: Vec<Slot<T>>,
+    // Indices of empty slots, reused by later inserts
+    free: Vec<usize>,
+}
+
+impl<T> StrongSet<T> {
+    pub fn new() -> Self {
+        StrongSet { slots: Vec::new(), free: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Take ownership of a new entry holding `x`
+    pub fn insert(&mut self, x: T) -> StrongKey {
+        self.insert_entry(Box::pin(Entry::new(x)))
+    }
+
+    // Take ownership of an already pinned entry. It may already be
+    // registered somewhere; it stays registered.
+    pub fn insert_entry(&mut self, entry: Pin<Box<Entry<T>>>) -> StrongKey {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { generation: 0, entry: None });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.entry = Some(entry);
+        StrongKey { index, generation: slot.generation }
+    }
+
+    pub fn get(&self, key: StrongKey) -> Option<Pin<&Entry<T>>> {
+        let slot = self.slots.get(key.index).filter(|slot| slot.generation == key.generation)?;
+        slot.entry.as_ref().map(|entry| entry.as_ref())
+    }
+
+    // Register one member into `set`. Returns false if the key is unknown or
+    // the member already belongs to a collection.
+    pub fn register<S: Storage>(&self, key: StrongKey, set: Pin<&WeakSet<T, S>>) -> bool
+    where S::Meta: Default
+    {
+        match self.get(key) {
+            Some(entry) if !entry.is_registered() => {
+                set.insert(entry);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Register every member that isn't part of a collection yet into `set`,
+    // returning how many were added.
+    pub fn register_all<S: Storage>(&self, set: Pin<&WeakSet<T, S>>) -> usize
+    where S::Meta: Default
+    {
+        let mut count = 0;
+        for entry in self.iter() {
+            if !entry.is_registered() {
+                set.insert(entry);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Give up ownership of a member without dropping it, so it stays
+    // registered wherever it is.
+    pub fn release(&mut self, key: StrongKey) -> Option<Pin<Box<Entry<T>>>> {
+        let slot = self.slots.get_mut(key.index).filter(|slot| slot.generation == key.generation)?;
+        let entry = slot.entry.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index);
+        Some(entry)
+    }
+
+    // Drop a member, which removes it from its collection
+    pub fn remove(&mut self, key: StrongKey) -> bool {
+        self.release(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Pin<&Entry<T>>> {
+        self.slots.iter().filter_map(|slot| slot.entry.as_ref().map(|entry| entry.as_ref()))
+    }
+}
+
+impl<T> FromIterator<T> for StrongSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = StrongSet::new();
+        for x in iter {
+            set.insert(x);
+        }
+        set
+    }
+}
+
+impl<T> Default for StrongSet<T> {
+    fn default() -> Self {
+        StrongSet::new()
+    }
+}
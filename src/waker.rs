@@ -0,0 +1,110 @@
+// A registry of task wakers for hand-written async primitives (events,
+// broadcast conditions). A future registers its waker when it has to wait and
+// keeps the returned `Waiter` in its own state; dropping the future drops the
+// waiter, which takes the waker out of the set.
+//
+//     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+//         if self.event.is_set() {
+//             return Poll::Ready(());
+//         }
+//         let this = &mut *self;
+//         let wakers = &this.event.wakers;
+//         this.waiter.get_or_insert_with(|| wakers.register(cx.waker())).update(cx.waker());
+//         Poll::Pending
+//     }
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::pin::Pin;
+use core::task::Waker;
+
+use crate::{Entry, Storage, WeakSet};
+
+pub struct WakerSet {
+    set: Pin<Box<WeakSet<RefCell<Waker>>>>,
+}
+
+pub struct Waiter {
+    entry: Pin<Box<Entry<RefCell<Waker>>>>,
+}
+
+impl WakerSet {
+    pub fn new() -> Self {
+        WakerSet { set: Box::pin(WeakSet::new()) }
+    }
+
+    pub fn register(&self, waker: &Waker) -> Waiter {
+        let entry = Box::pin(Entry::new(RefCell::new(waker.clone())));
+        self.set.as_ref().insert(entry.as_ref());
+        Waiter { entry }
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    // Wake every registered waiter, returning how many were woken. Wakers
+    // are cloned out first so that waking (which may run arbitrary executor
+    // code) happens with the set unborrowed.
+    pub fn wake_all(&self) -> usize {
+        let wakers: Vec<Waker> = self.set.as_ref().iter().map(|entry| entry.borrow().clone()).collect();
+        let n = wakers.len();
+        for waker in wakers {
+            waker.wake();
+        }
+        n
+    }
+
+    // Wake one registered waiter, if there is any
+    pub fn wake_one(&self) -> bool {
+        let waker = self.set.as_ref().iter().next().map(|entry| entry.borrow().clone());
+        waker.map(Waker::wake).is_some()
+    }
+}
+
+impl Default for WakerSet {
+    fn default() -> Self {
+        WakerSet::new()
+    }
+}
+
+impl Waiter {
+    // Replace the stored waker, e.g. when the future is polled again by a
+    // different task
+    pub fn update(&self, waker: &Waker) {
+        let mut current = self.entry.borrow_mut();
+        if !current.will_wake(waker) {
+            *current = waker.clone();
+        }
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.entry.is_registered()
+    }
+}
+
+// For sets that hold plain wakers. Each waker is cloned out under a short
+// borrow and woken with the set unborrowed. Waking doesn't remove anything;
+// the member leaves when its owner drops it.
+impl<S: Storage> WeakSet<Waker, S> {
+    pub fn wake_all(self: Pin<&Self>) -> usize {
+        let wakers: Vec<Waker> = self.iter().map(|entry| (**entry).clone()).collect();
+        let n = wakers.len();
+        for waker in wakers {
+            waker.wake();
+        }
+        n
+    }
+
+    // Wake the first member in iteration order. Returns false if the set
+    // is empty.
+    pub fn wake_one(self: Pin<&Self>) -> bool {
+        let waker = self.iter().next().map(|entry| (**entry).clone());
+        waker.map(Waker::wake).is_some()
+    }
+}
@@ -0,0 +1,146 @@
+// A set that keeps the total of a weight each member carries, e.g. the bytes
+// held by every registered buffer:
+//
+//     let buffers: WeakSet<Buffer, WeightedStorage> = WeakSet::new_weighted();
+//     buffers.as_ref().insert_with_weight(buf.as_ref(), buf.len() as u64);
+//     if buffers.total_weight() > BUDGET { ... }
+//
+// The total is kept by the storage, so a member that drops takes its weight
+// out along with it. Members inserted through the plain `insert` weigh 0.
+
+use core::pin::Pin;
+
+use crate::storage::{DenseIter, DenseStorage, Storage};
+use crate::{Entry, EntryId, WeakSet};
+
+pub struct WeightedStorage<M = ()> {
+    dense: DenseStorage<(u64, M)>,
+    total: u64,
+}
+
+impl<M> Default for WeightedStorage<M> {
+    fn default() -> Self {
+        WeightedStorage { dense: DenseStorage::new(), total: 0 }
+    }
+}
+
+impl<M> WeightedStorage<M> {
+    // Re-inserting a member replaces its weight.
+    //
+    // Safety: as for `Storage::insert`.
+    pub unsafe fn insert_with_weight(&mut self, entry: *const (), weight: u64, meta: M) {
+        if let Some(&(old, _)) = self.dense.get(entry) {
+            self.total -= old;
+        }
+        self.total += weight;
+        self.dense.insert(entry, (weight, meta));
+    }
+
+    // Safety: as for `Storage::get`.
+    pub unsafe fn weight(&self, entry: *const ()) -> Option<u64> {
+        self.dense.get(entry).map(|&(weight, _)| weight)
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.total
+    }
+}
+
+pub struct WeightedIter<'a, M> {
+    base: DenseIter<'a, (u64, M)>,
+}
+
+impl<'a, M> Iterator for WeightedIter<'a, M> {
+    type Item = (*const (), &'a M);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base.next().map(|(entry, (_, meta))| (entry, meta))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<'a, M> DoubleEndedIterator for WeightedIter<'a, M> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.base.next_back().map(|(entry, (_, meta))| (entry, meta))
+    }
+}
+
+impl<'a, M> ExactSizeIterator for WeightedIter<'a, M> {}
+
+unsafe impl<M> Storage for WeightedStorage<M> {
+    type Meta = M;
+    type Iter<'a> = WeightedIter<'a, M>
+    where M: 'a;
+
+    // Keeps the weight of a member that is already here
+    unsafe fn insert(&mut self, entry: *const (), meta: M) {
+        let weight = self.weight(entry).unwrap_or(0);
+        self.insert_with_weight(entry, weight, meta);
+    }
+
+    unsafe fn remove(&mut self, entry: *const ()) -> Option<M> {
+        let (weight, meta) = self.dense.remove(entry)?;
+        self.total -= weight;
+        Some(meta)
+    }
+
+    unsafe fn get(&self, entry: *const ()) -> Option<&M> {
+        self.dense.get(entry).map(|(_, meta)| meta)
+    }
+
+    unsafe fn get_mut(&mut self, entry: *const ()) -> Option<&mut M> {
+        self.dense.get_mut(entry).map(|(_, meta)| meta)
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        WeightedIter { base: self.dense.iter() }
+    }
+
+    fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+    }
+
+    fn shrink_to(&mut self, min: usize) {
+        self.dense.shrink_to(min);
+    }
+
+    fn heap_size(&self) -> usize {
+        self.dense.heap_size()
+    }
+}
+
+impl<T, M> WeakSet<T, WeightedStorage<M>> {
+    pub fn new_weighted() -> Self {
+        WeakSet::with_storage(WeightedStorage::default())
+    }
+
+    #[cfg_attr(feature = "history", track_caller)]
+    pub fn insert_with_weight(self: Pin<&Self>, entry: Pin<&Entry<T>>, weight: u64) -> EntryId
+    where M: Default
+    {
+        self.link(entry, |objects, ptr| unsafe { objects.insert_with_weight(ptr, weight, M::default()) });
+        entry.id()
+    }
+
+    pub fn weight(self: Pin<&Self>, entry: Pin<&Entry<T>>) -> Option<u64> {
+        unsafe { self.objects.borrow().weight(&*entry as *const Entry<T> as *const ()) }
+    }
+
+    // The weights of the current members added up
+    pub fn total_weight(&self) -> u64 {
+        self.objects.borrow().total_weight()
+    }
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "critical-section")]
+
+use std::cell::Cell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::thread;
+
+use weakset::{CriticalWeakSet, Entry};
+
+static SET: CriticalWeakSet<u32> = CriticalWeakSet::new();
+
+#[test]
+fn clearing_from_another_thread_leaves_detach_hooks_to_the_owner() {
+    let set = Pin::static_ref(&SET);
+    let entry = Box::pin(Entry::new(1));
+    let ran = Rc::new(Cell::new(false));
+    let hook = ran.clone();
+    entry.on_detached(move || hook.set(true));
+    set.insert(entry.as_ref());
+
+    thread::spawn(move || set.clear()).join().unwrap();
+    assert!(!entry.is_registered());
+    assert!(!ran.get());
+    assert_eq!(Rc::strong_count(&ran), 2);
+
+    // Kept for the next time the entry leaves a set
+    set.insert(entry.as_ref());
+    set.remove(entry.as_ref());
+    assert!(ran.get());
+}
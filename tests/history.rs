@@ -0,0 +1,45 @@
+#![cfg(feature = "history")]
+
+use weakset::history::Change;
+use weakset::{Entry, WeakSet};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn history_records_changes_and_their_callers() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let b = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().insert(b.as_ref());
+    let line = line!() + 1;
+    set.as_ref().remove(a.as_ref());
+    let b_id = b.id();
+    drop(b);
+
+    let history = set.history();
+    let changes: Vec<_> = history.iter().map(|record| (record.seq, record.change)).collect();
+    assert_eq!(changes, [(0, Change::Inserted), (1, Change::Inserted), (2, Change::Removed), (3, Change::Dropped)]);
+    let removed = history[2].location.unwrap();
+    assert_eq!((removed.file(), removed.line()), (file!(), line));
+    assert_eq!(history[3].id, b_id);
+    assert!(history[3].location.is_none());
+}
+
+#[test]
+fn history_keeps_only_the_latest_changes() {
+    let set = Box::pin(WeakSet::new());
+    set.set_history_capacity(2);
+    let entries: Vec<_> = (0..3).map(|x| Box::pin(Entry::new(x))).collect();
+    for entry in &entries {
+        set.as_ref().insert(entry.as_ref());
+    }
+    let seqs: Vec<u64> = set.history().iter().map(|record| record.seq).collect();
+    assert_eq!(seqs, [1, 2]);
+
+    let mut dump = String::new();
+    set.dump_history(&mut dump).unwrap();
+    assert_eq!(dump.lines().count(), 2);
+    assert!(dump.starts_with("#1 inserted"));
+}
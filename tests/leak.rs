@@ -0,0 +1,50 @@
+#![cfg(feature = "leak-check")]
+
+use std::mem;
+
+use weakset::{leak, Entry, WeakSet};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+// The counts are process-wide, so everything runs in one test
+#[test]
+fn leaks_are_reported_and_counted() {
+    // Members that leave one way or another aren't leaks
+    {
+        let set = Box::pin(WeakSet::new());
+        let a = Box::pin(Entry::new(1));
+        let b = Box::pin(Entry::new(2));
+        let c = Box::pin(Entry::new(3));
+        set.as_ref().insert(a.as_ref());
+        set.as_ref().insert(b.as_ref());
+        set.as_ref().insert(c.as_ref());
+        assert_eq!(leak::registered(), 3);
+        set.as_ref().remove(a.as_ref());
+        drop(b);
+        set.as_ref().clear_matching(|_| true);
+        assert_eq!(leak::registered(), 0);
+    }
+    assert_eq!(leak::leaked(), 0);
+
+    // A forgotten member stays registered, and is reported with its set
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    set.as_ref().insert(a.as_ref());
+    mem::forget(a);
+    assert_eq!(leak::registered(), 1);
+    drop(set);
+    assert_eq!(leak::leaked(), 1);
+    assert_eq!(leak::registered(), 0);
+
+    leak::reset_leaked();
+    leak::set_hook(leak::panic);
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    set.as_ref().insert(a.as_ref());
+    let dropped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(set)));
+    assert!(dropped.is_err());
+    assert_eq!(leak::leaked(), 0);
+    assert_eq!(leak::registered(), 0);
+    leak::reset_hook();
+}
@@ -0,0 +1,302 @@
+// Random operation sequences run against real sets and against a model that
+// only tracks which entries are alive and which set each belongs to. After
+// every step the sets have to agree with the model on everything observable.
+// Half the sequences work on a few entries, the other half on enough of them
+// (in bulk) for the storages to grow past their minimum size and shrink back.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::cell::Cell;
+use std::ops::Range;
+use std::pin::Pin;
+
+use proptest::prelude::*;
+use weakset::{
+    DenseStorage, Entry, HashStorage, InlineStorage, KeyedEntry, KeyedStorage, OpenStorage, PriorityStorage,
+    SortedStorage, Storage, WeakSet, WeightedStorage,
+};
+
+const ENTRIES: usize = 8;
+// Enough members for any storage to be well past its minimum capacity
+const MANY: usize = 160;
+const SETS: usize = 2;
+
+// Each entry holds its own index, which is also its key
+struct Item(usize);
+
+impl KeyedEntry for Item {
+    type Key = usize;
+
+    fn key(&self) -> &usize {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Create(usize),
+    Drop(usize),
+    Insert(usize, usize),
+    Remove(usize, usize),
+    RemoveById(usize, usize),
+    Clear(usize),
+    ClearOdd(usize),
+    ShrinkToFit(usize),
+    // Broadcast over a set, removing one entry from it on the first call
+    BroadcastRemoving(usize, usize),
+    // The single-entry ops above on a run of entries
+    CreateRange(Range<usize>),
+    DropRange(Range<usize>),
+    InsertRange(usize, Range<usize>),
+    RemoveRange(usize, Range<usize>),
+}
+
+fn op(entries: usize) -> impl Strategy<Value = Op> {
+    let entry = 0..entries;
+    let set = 0..SETS;
+    let range = (0..entries, 0..entries).prop_map(|(a, b)| a.min(b)..a.max(b) + 1);
+    prop_oneof![
+        3 => entry.clone().prop_map(Op::Create),
+        2 => entry.clone().prop_map(Op::Drop),
+        4 => (set.clone(), entry.clone()).prop_map(|(s, e)| Op::Insert(s, e)),
+        2 => (set.clone(), entry.clone()).prop_map(|(s, e)| Op::Remove(s, e)),
+        1 => (set.clone(), entry.clone()).prop_map(|(s, e)| Op::RemoveById(s, e)),
+        1 => set.clone().prop_map(Op::Clear),
+        1 => set.clone().prop_map(Op::ClearOdd),
+        1 => set.clone().prop_map(Op::ShrinkToFit),
+        1 => (set.clone(), entry).prop_map(|(s, e)| Op::BroadcastRemoving(s, e)),
+        3 => range.clone().prop_map(Op::CreateRange),
+        1 => range.clone().prop_map(Op::DropRange),
+        4 => (set.clone(), range.clone()).prop_map(|(s, r)| Op::InsertRange(s, r)),
+        1 => (set, range).prop_map(|(s, r)| Op::RemoveRange(s, r)),
+    ]
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    // The long runs start with every entry alive, so the first bulk inserts
+    // already make the sets big
+    let many = prop::collection::vec(op(MANY), 0..32).prop_map(|mut ops| {
+        ops.insert(0, Op::CreateRange(0..MANY));
+        ops
+    });
+    prop_oneof![prop::collection::vec(op(ENTRIES), 0..64), many]
+}
+
+// What the model knows about each entry
+#[derive(Clone, Copy, Default)]
+struct Model {
+    alive: bool,
+    member_of: Option<usize>,
+}
+
+type Sets<S> = [Pin<Box<WeakSet<Item, S>>>];
+type Slots = [Option<Pin<Box<Entry<Item>>>>];
+
+fn run<S: Storage>(make: impl Fn() -> S, ops: &[Op])
+where S::Meta: Default
+{
+    run_checking(make, ops, |_, _| {});
+}
+
+// `extra` checks what only some storages can tell, after the common checks
+fn run_checking<S: Storage>(make: impl Fn() -> S, ops: &[Op], extra: impl Fn(&Sets<S>, &[Model]))
+where S::Meta: Default
+{
+    let sets: Vec<Pin<Box<WeakSet<Item, S>>>> = (0..SETS).map(|_| Box::pin(WeakSet::with_storage(make()))).collect();
+    let mut entries: Vec<Option<Pin<Box<Entry<Item>>>>> = (0..MANY).map(|_| None).collect();
+    let mut model = [Model::default(); MANY];
+
+    for op in ops {
+        apply(op, &sets, &mut entries, &mut model);
+        check(&sets, &entries, &model);
+        extra(&sets, &model);
+    }
+}
+
+fn apply<S: Storage>(op: &Op, sets: &Sets<S>, entries: &mut Slots, model: &mut [Model])
+where S::Meta: Default
+{
+    match *op {
+        Op::Create(e) => {
+            entries[e] = Some(Box::pin(Entry::new(Item(e))));
+            model[e] = Model { alive: true, member_of: None };
+        }
+        Op::Drop(e) => {
+            entries[e] = None;
+            model[e] = Model::default();
+        }
+        Op::Insert(s, e) => {
+            // An entry already in a set can't be inserted anywhere
+            if let (Some(entry), None) = (&entries[e], model[e].member_of) {
+                let id = sets[s].as_ref().insert(entry.as_ref());
+                assert_eq!(id, entry.id());
+                model[e].member_of = Some(s);
+            }
+        }
+        Op::Remove(s, e) => {
+            if let Some(entry) = &entries[e] {
+                let removed = sets[s].as_ref().remove(entry.as_ref());
+                assert_eq!(removed, model[e].member_of == Some(s));
+                if removed {
+                    model[e].member_of = None;
+                }
+            }
+        }
+        Op::RemoveById(s, e) => {
+            // Ids of dropped entries may be reused, so only live ones
+            if let Some(entry) = &entries[e] {
+                let removed = sets[s].as_ref().remove_by_id(entry.id());
+                assert_eq!(removed, model[e].member_of == Some(s));
+                if removed {
+                    model[e].member_of = None;
+                }
+            }
+        }
+        Op::Clear(s) => {
+            sets[s].as_ref().clear();
+            for m in model.iter_mut().filter(|m| m.member_of == Some(s)) {
+                m.member_of = None;
+            }
+        }
+        Op::ClearOdd(s) => {
+            let removed = sets[s].as_ref().clear_matching(|x| x.0 % 2 == 1);
+            let mut expected = 0;
+            for (x, m) in model.iter_mut().enumerate() {
+                if m.member_of == Some(s) && x % 2 == 1 {
+                    m.member_of = None;
+                    expected += 1;
+                }
+            }
+            assert_eq!(removed, expected);
+        }
+        Op::ShrinkToFit(s) => sets[s].shrink_to_fit(),
+        Op::BroadcastRemoving(s, e) => {
+            let before = members(model, s);
+            let mut seen = Vec::new();
+            let first = Cell::new(true);
+            sets[s].as_ref().broadcast(|x| {
+                seen.push(x.0);
+                if first.replace(false) {
+                    if let Some(entry) = &entries[e] {
+                        sets[s].as_ref().remove(entry.as_ref());
+                    }
+                }
+            });
+            if model[e].member_of == Some(s) {
+                model[e].member_of = None;
+            }
+            // Everyone but the removed entry is visited exactly once, and
+            // that one at most once
+            seen.sort();
+            let mut rest: Vec<usize> = seen.iter().copied().filter(|&x| x != e).collect();
+            rest.dedup();
+            assert_eq!(rest.len(), seen.iter().filter(|&&x| x != e).count());
+            let expected: Vec<usize> = before.iter().copied().filter(|&x| x != e).collect();
+            assert_eq!(rest, expected);
+            assert!(seen.iter().filter(|&&x| x == e).count() <= usize::from(before.contains(&e)));
+        }
+        Op::CreateRange(ref r) => r.clone().for_each(|e| apply(&Op::Create(e), sets, entries, model)),
+        Op::DropRange(ref r) => r.clone().for_each(|e| apply(&Op::Drop(e), sets, entries, model)),
+        Op::InsertRange(s, ref r) => r.clone().for_each(|e| apply(&Op::Insert(s, e), sets, entries, model)),
+        Op::RemoveRange(s, ref r) => r.clone().for_each(|e| apply(&Op::Remove(s, e), sets, entries, model)),
+    }
+}
+
+fn members(model: &[Model], s: usize) -> Vec<usize> {
+    (0..model.len()).filter(|&x| model[x].member_of == Some(s)).collect()
+}
+
+fn check<S: Storage>(sets: &Sets<S>, entries: &Slots, model: &[Model]) {
+    for (s, set) in sets.iter().enumerate() {
+        let expected = members(model, s);
+        assert_eq!(set.len(), expected.len());
+        assert_eq!(set.is_empty(), expected.is_empty());
+
+        let mut visited = Vec::new();
+        set.as_ref().for_each(|x| visited.push(x.0));
+        visited.sort();
+        assert_eq!(visited, expected);
+    }
+    for (e, entry) in entries.iter().enumerate() {
+        assert_eq!(entry.is_some(), model[e].alive);
+        if let Some(entry) = entry {
+            assert_eq!(entry.is_registered(), model[e].member_of.is_some());
+            for (s, set) in sets.iter().enumerate() {
+                assert_eq!(set.contains(entry), model[e].member_of == Some(s));
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn dense_matches_model(ops in ops()) {
+        run(<DenseStorage>::default, &ops);
+    }
+
+    #[test]
+    fn sorted_matches_model(ops in ops()) {
+        run(<SortedStorage>::default, &ops);
+    }
+
+    #[test]
+    fn inline_matches_model(ops in ops()) {
+        run(<InlineStorage>::default, &ops);
+    }
+
+    #[test]
+    fn hash_matches_model(ops in ops()) {
+        run(<HashStorage>::default, &ops);
+    }
+
+    #[test]
+    fn open_matches_model(ops in ops()) {
+        run(<OpenStorage>::default, &ops);
+    }
+
+    #[test]
+    fn incremental_open_matches_model(ops in ops()) {
+        run(<OpenStorage>::incremental, &ops);
+    }
+
+    #[test]
+    fn priority_matches_model(ops in ops()) {
+        run(<PriorityStorage>::default, &ops);
+    }
+
+    #[test]
+    fn weighted_matches_model(ops in ops()) {
+        run(<WeightedStorage>::default, &ops);
+    }
+
+    #[test]
+    fn keyed_matches_model(ops in ops()) {
+        run_checking(<KeyedStorage<Item>>::default, &ops, |sets, model| {
+            for (s, set) in sets.iter().enumerate() {
+                for (e, m) in model.iter().enumerate() {
+                    let found = set.as_ref().with_by_key(&e, |x| x.0);
+                    assert_eq!(found, Some(e).filter(|_| m.member_of == Some(s)));
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn fixed_matches_model(ops in ops()) {
+        run(weakset::fixed::FixedStorage::<(), MANY>::default, &ops);
+    }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn bump_matches_model(ops in ops()) {
+        let bump = bumpalo::Bump::new();
+        run(|| weakset::bump::BumpStorage::<()>::new_in(&bump), &ops);
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn alloc_matches_model(ops in ops()) {
+        run(|| weakset::allocator::AllocStorage::<_, ()>::new_in(allocator_api2::alloc::Global), &ops);
+    }
+}
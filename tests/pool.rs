@@ -0,0 +1,30 @@
+// Unwinding isn't supported on wasm
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use weakset::EntryPool;
+
+struct Panics(Rc<Cell<u32>>);
+
+impl Drop for Panics {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+        panic!("payload drop");
+    }
+}
+
+#[test]
+fn slot_is_released_when_the_payload_drop_panics() {
+    let drops = Rc::new(Cell::new(0));
+    let pool = EntryPool::new();
+    let pooled = pool.alloc(Panics(drops.clone()));
+    let key = pooled.key();
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(pooled))).is_err());
+    assert!(!pool.contains(key));
+    assert!(pool.is_empty());
+    drop(pool);
+    assert_eq!(drops.get(), 1);
+}
@@ -0,0 +1,252 @@
+use std::cell::{Cell, RefCell};
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use weakset::{Connection, Entry, Interned, Signal, WeakInterner, WeakMap, WeakSet};
+
+// Run under wasm-bindgen's runner on wasm, which doesn't pick up `#[test]`
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn member_inserted_during_broadcast_waits_for_the_next() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let late = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    let mut seen = Vec::new();
+    set.as_ref().broadcast(|&x| {
+        seen.push(x);
+        if !late.is_registered() {
+            set.as_ref().insert(late.as_ref());
+        }
+    });
+    assert_eq!(seen, [1]);
+    assert_eq!(set.len(), 2);
+    set.as_ref().broadcast(|&x| seen.push(x));
+    seen.sort();
+    assert_eq!(seen, [1, 1, 2]);
+}
+
+#[test]
+fn member_removed_during_broadcast_is_skipped() {
+    let set = Box::pin(WeakSet::new());
+    let entries: Vec<Pin<Box<Entry<i32>>>> = (0..4).map(|x| Box::pin(Entry::new(x))).collect();
+    for entry in &entries {
+        set.as_ref().insert(entry.as_ref());
+    }
+    // Whichever member comes first takes out all the others
+    let mut calls = 0;
+    set.as_ref().broadcast(|&x| {
+        calls += 1;
+        for entry in entries.iter().filter(|entry| ****entry != x) {
+            set.as_ref().remove(entry.as_ref());
+        }
+    });
+    assert_eq!(calls, 1);
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn member_dropped_during_broadcast_is_skipped() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let b = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().insert(b.as_ref());
+    let slots = RefCell::new(vec![Some(a), Some(b)]);
+    let mut calls = 0;
+    set.as_ref().broadcast(|&x| {
+        calls += 1;
+        // drop the other member
+        let other = if x == 1 { 1 } else { 0 };
+        slots.borrow_mut()[other].take();
+    });
+    assert_eq!(calls, 1);
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn member_removes_itself_during_broadcast() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().broadcast(|_| {
+        set.as_ref().remove(a.as_ref());
+    });
+    assert!(set.is_empty());
+    assert!(!a.is_registered());
+}
+
+#[test]
+fn nested_broadcasts_see_their_own_snapshot() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let b = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().insert(b.as_ref());
+    let outer = Cell::new(0);
+    let inner = Cell::new(0);
+    set.as_ref().broadcast(|_| {
+        outer.set(outer.get() + 1);
+        set.as_ref().broadcast(|_| inner.set(inner.get() + 1));
+    });
+    assert_eq!(outer.get(), 2);
+    assert_eq!(inner.get(), 4);
+}
+
+#[test]
+fn slot_disconnected_before_its_turn_is_not_called() {
+    let signal = Signal::new();
+    let conns: Rc<RefCell<Vec<Connection<()>>>> = Rc::new(RefCell::new(Vec::new()));
+    let calls = Rc::new(Cell::new(0));
+    for _ in 0..3 {
+        let (c, n) = (conns.clone(), calls.clone());
+        // Whichever slot runs first disconnects everyone, itself included
+        let conn = signal.connect(move |_: &()| {
+            n.set(n.get() + 1);
+            c.borrow_mut().clear();
+        });
+        conns.borrow_mut().push(conn);
+    }
+    signal.emit(&());
+    assert_eq!(calls.get(), 1);
+    assert!(signal.is_empty());
+}
+
+#[test]
+fn slot_connected_and_disconnected_in_one_emission() {
+    let signal = Rc::new(Signal::new());
+    let calls = Rc::new(Cell::new(0));
+    let (s, n) = (signal.clone(), calls.clone());
+    let _conn = signal.connect(move |_: &()| {
+        let n = n.clone();
+        let transient = s.connect(move |_: &()| n.set(n.get() + 1));
+        drop(transient);
+    });
+    signal.emit(&());
+    signal.emit(&());
+    assert_eq!(calls.get(), 0);
+    assert_eq!(signal.len(), 1);
+}
+
+#[test]
+fn emit_from_inside_a_slot() {
+    let signal: Rc<Signal<u32>> = Rc::new(Signal::new());
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let (s, l) = (signal.clone(), log.clone());
+    let _conn = signal.connect(move |&depth: &u32| {
+        l.borrow_mut().push(depth);
+        if depth < 2 {
+            s.emit(&(depth + 1));
+        }
+    });
+    signal.emit(&0);
+    assert_eq!(*log.borrow(), [0, 1, 2]);
+}
+
+#[test]
+fn member_dropped_by_a_remove_hook_during_clear_is_counted_once() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let b = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().insert(b.as_ref());
+    // Whichever member is notified first drops the other, which is still
+    // waiting for its turn
+    let slots = Rc::new(RefCell::new(vec![Some(a), Some(b)]));
+    let removed = Rc::new(Cell::new(0));
+    let (hook_slots, hook_removed) = (slots.clone(), removed.clone());
+    set.on_remove(move |&x| {
+        hook_removed.set(hook_removed.get() + 1);
+        let other = if x == 1 { 1 } else { 0 };
+        hook_slots.borrow_mut()[other].take();
+    });
+    assert_eq!(set.as_ref().clear_matching(|_| true), 2);
+    // The hook doesn't hear about removals it makes itself
+    assert_eq!(removed.get(), 1);
+    let stats = set.stats();
+    assert_eq!((stats.removals, stats.drop_removals), (2, 0));
+    assert!(set.is_empty());
+}
+
+#[test]
+fn map_value_made_by_dropping_another_keys_last_handle() {
+    let map = WeakMap::new();
+    let old = RefCell::new(Some(map.get_or_insert_with(1, || "one")));
+    let two = map.get_or_insert_with(2, || {
+        old.borrow_mut().take();
+        "two"
+    });
+    assert_eq!(*two, "two");
+    assert!(!map.contains_key(&1));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn map_value_made_by_registering_its_own_key() {
+    let map = WeakMap::new();
+    let mut inner = None;
+    let outer = map.get_or_insert_with(1, || {
+        inner = Some(map.get_or_insert_with(1, || "inner"));
+        "outer"
+    });
+    assert!(outer == inner.unwrap());
+    assert_eq!(*outer, "inner");
+    assert_eq!(map.len(), 1);
+}
+
+// Unwinding isn't supported on wasm
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn remove_hook_survives_its_own_panic() {
+    let set = Box::pin(WeakSet::new());
+    let a = Box::pin(Entry::new(1));
+    let b = Box::pin(Entry::new(2));
+    set.as_ref().insert(a.as_ref());
+    set.as_ref().insert(b.as_ref());
+    let removed = Rc::new(RefCell::new(Vec::new()));
+    let hook_removed = removed.clone();
+    set.on_remove(move |&x| {
+        hook_removed.borrow_mut().push(x);
+        assert_ne!(x, 1);
+    });
+    let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| set.as_ref().remove(a.as_ref())));
+    assert!(caught.is_err());
+    set.as_ref().remove(b.as_ref());
+    assert_eq!(*removed.borrow(), [1, 2]);
+}
+
+// Comparing names drops the handle in `VICTIM`
+struct Name(&'static str);
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        VICTIM.with(|victim| victim.borrow_mut().take());
+        self.0 == other.0
+    }
+}
+
+impl Eq for Name {}
+
+thread_local! {
+    static VICTIM: RefCell<Option<Interned<Name>>> = const { RefCell::new(None) };
+}
+
+#[test]
+fn interned_value_dropped_by_a_comparison() {
+    let names = WeakInterner::new();
+    let x = names.intern(Name("x"));
+    VICTIM.with(|victim| *victim.borrow_mut() = Some(names.intern(Name("y"))));
+    assert_eq!(names.len(), 2);
+    assert!(names.intern(Name("x")) == x);
+    assert_eq!(names.len(), 1);
+    assert!(names.get(&Name("y")).is_none());
+}
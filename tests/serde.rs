@@ -0,0 +1,54 @@
+#![cfg(feature = "serde")]
+
+use weakset::{Entry, StrongSet, WeakSet};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn set_serializes_as_its_payloads() {
+    let set = Box::pin(WeakSet::<String>::new());
+    let a = Box::pin(Entry::new("a".to_string()));
+    let b = Box::pin(Entry::new("b".to_string()));
+    let c = Box::pin(Entry::new("c".to_string()));
+    for entry in [&a, &b, &c] {
+        set.as_ref().insert(entry.as_ref());
+    }
+    drop(b);
+    let mut names: Vec<String> = serde_json::from_str(&serde_json::to_string(&set.as_ref()).unwrap()).unwrap();
+    names.sort();
+    assert_eq!(names, ["a", "c"]);
+}
+
+#[test]
+fn empty_set_serializes_as_empty_sequence() {
+    let set = Box::pin(WeakSet::<u32>::new());
+    assert_eq!(serde_json::to_string(&set.as_ref()).unwrap(), "[]");
+}
+
+#[test]
+fn snapshot_round_trips_into_a_registered_set() {
+    let set = Box::pin(WeakSet::<u32>::new());
+    let entries: Vec<_> = (0..4).map(|i| Box::pin(Entry::new(i))).collect();
+    for entry in &entries {
+        set.as_ref().insert(entry.as_ref());
+    }
+    let json = serde_json::to_string(&set.as_ref()).unwrap();
+
+    let members: StrongSet<u32> = serde_json::from_str(&json).unwrap();
+    let replayed = Box::pin(WeakSet::<u32>::new());
+    assert_eq!(members.register_all(replayed.as_ref()), 4);
+    let mut values = Vec::new();
+    replayed.as_ref().for_each(|x| values.push(*x));
+    values.sort();
+    assert_eq!(values, [0, 1, 2, 3]);
+
+    // the owning set keeps the replayed members alive until it goes away
+    drop(members);
+    assert!(replayed.is_empty());
+}
+
+#[test]
+fn non_sequence_is_rejected() {
+    assert!(serde_json::from_str::<StrongSet<u32>>("{}").is_err());
+}
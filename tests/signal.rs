@@ -0,0 +1,107 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use weakset::{Connection, Signal, Slot};
+
+// Run under wasm-bindgen's runner on wasm, which doesn't pick up `#[test]`
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn emit_reaches_every_slot() {
+    let signal = Signal::new();
+    let total = Rc::new(Cell::new(0));
+    let t1 = total.clone();
+    let t2 = total.clone();
+    let _a = signal.connect(move |x: &i32| t1.set(t1.get() + *x));
+    let _b = signal.connect(move |x: &i32| t2.set(t2.get() + *x * 10));
+    signal.emit(&1);
+    assert_eq!(total.get(), 11);
+    assert_eq!(signal.len(), 2);
+}
+
+#[test]
+fn dropping_connection_unsubscribes() {
+    let signal = Signal::new();
+    let hits = Rc::new(Cell::new(0));
+    let h = hits.clone();
+    let conn = signal.connect(move |_: &()| h.set(h.get() + 1));
+    signal.emit(&());
+    assert!(conn.is_connected());
+    conn.disconnect();
+    signal.emit(&());
+    assert_eq!(hits.get(), 1);
+    assert!(signal.is_empty());
+}
+
+#[test]
+fn slot_objects() {
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+    impl Slot<str> for Recorder {
+        fn call(&self, args: &str) {
+            self.0.borrow_mut().push(args.to_owned());
+        }
+    }
+
+    let signal: Signal<str> = Signal::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let _conn = signal.connect(Recorder(log.clone()));
+    signal.emit("hello");
+    signal.emit("world");
+    assert_eq!(*log.borrow(), ["hello", "world"]);
+}
+
+#[test]
+fn slot_can_disconnect_itself() {
+    let signal = Rc::new(Signal::new());
+    let conn: Rc<RefCell<Option<Connection<()>>>> = Rc::new(RefCell::new(None));
+    let hits = Rc::new(Cell::new(0));
+    let (c, h) = (conn.clone(), hits.clone());
+    *conn.borrow_mut() = Some(signal.connect(move |_: &()| {
+        h.set(h.get() + 1);
+        c.borrow_mut().take();
+    }));
+    signal.emit(&());
+    signal.emit(&());
+    assert_eq!(hits.get(), 1);
+    assert!(signal.is_empty());
+}
+
+#[test]
+fn slot_connected_during_emit_runs_next_time() {
+    let signal = Rc::new(Signal::new());
+    let late: Rc<RefCell<Vec<Connection<()>>>> = Rc::new(RefCell::new(Vec::new()));
+    let hits = Rc::new(Cell::new(0));
+    let (s, l, h) = (Rc::downgrade(&signal), late.clone(), hits.clone());
+    let _first = signal.connect(move |_: &()| {
+        let h = h.clone();
+        let signal = s.upgrade().unwrap();
+        l.borrow_mut().push(signal.connect(move |_: &()| h.set(h.get() + 1)));
+    });
+    signal.emit(&());
+    assert_eq!(hits.get(), 0);
+    signal.emit(&());
+    assert_eq!(hits.get(), 1);
+    assert_eq!(signal.len(), 3);
+}
+
+#[test]
+fn connection_outlives_signal() {
+    let signal = Signal::new();
+    let conn = signal.connect(|_: &u8| {});
+    drop(signal);
+    assert!(!conn.is_connected());
+}
+
+#[test]
+fn slots_run_in_priority_order() {
+    let signal = Signal::new();
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let mut conns = Vec::new();
+    for (name, priority) in [("low", -1), ("a", 0), ("high", 5), ("b", 0)] {
+        let order = order.clone();
+        conns.push(signal.connect_with_priority(move |_: &()| order.borrow_mut().push(name), priority));
+    }
+    signal.emit(&());
+    assert_eq!(*order.borrow(), ["high", "a", "b", "low"]);
+}
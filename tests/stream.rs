@@ -0,0 +1,47 @@
+#![cfg(feature = "futures-core")]
+
+use std::cell::Cell;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use futures_core::Stream;
+use weakset::{Entry, WeakSet};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+// A waker that opens another stream on the set when woken
+struct Probe<'a> {
+    set: &'a WeakSet<u32>,
+    woken: Cell<u32>,
+}
+
+fn probe_waker(probe: &Probe<'_>) -> Waker {
+    unsafe fn clone(data: *const ()) -> RawWaker {
+        RawWaker::new(data, &VTABLE)
+    }
+    unsafe fn wake(data: *const ()) {
+        let probe = &*(data as *const Probe<'_>);
+        probe.woken.set(probe.woken.get() + 1);
+        drop(probe.set.changes_stream());
+    }
+    unsafe fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, noop);
+    // The waker doesn't outlive the probe in the test below
+    unsafe { Waker::from_raw(RawWaker::new(probe as *const Probe<'_> as *const (), &VTABLE)) }
+}
+
+#[test]
+fn waker_may_use_the_set() {
+    let set = Box::pin(WeakSet::new());
+    let mut stream = set.changes_stream();
+    let probe = Probe { set: &set, woken: Cell::new(0) };
+    let waker = probe_waker(&probe);
+    let mut cx = Context::from_waker(&waker);
+    assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+
+    let entry = Box::pin(Entry::new(1));
+    set.as_ref().insert(entry.as_ref());
+    assert_eq!(probe.woken.get(), 1);
+    assert!(matches!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(Some(Ok(_)))));
+}
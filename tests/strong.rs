@@ -0,0 +1,17 @@
+use weakset::StrongSet;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn stale_key_misses_the_reused_slot() {
+    let mut members = StrongSet::new();
+    let old = members.insert(1);
+    assert!(members.remove(old));
+    let new = members.insert(2);
+    assert!(members.get(old).is_none());
+    assert!(!members.remove(old));
+    assert!(members.release(old).is_none());
+    assert_eq!(members.get(new).map(|entry| **entry), Some(2));
+    assert_eq!(members.len(), 1);
+}